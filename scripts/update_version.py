@@ -14,28 +14,29 @@ def update_version():
         version = tauri_config.get('version', '0.0.0')  # 存在しない場合はデフォルト
 
     # 2. Cargo.toml の version を更新（既存の [package] version を置換）
-    cargo_toml_path = 'src-tauri/Cargo.toml'
-    if not os.path.exists(cargo_toml_path):
-        print(f"Warning: {cargo_toml_path} not found. Skipping Cargo.toml update.")
-    else:
-        with open(cargo_toml_path, 'r', encoding='utf-8') as f:
-            cargo_content = f.read()
+    # エンジン (cg-backup-core) と CLI (cg-backup-cli) もアプリと同じバージョンで管理する
+    for cargo_toml_path in ['src-tauri/Cargo.toml', 'src-tauri/cg-backup-core/Cargo.toml', 'src-tauri/cg-backup-cli/Cargo.toml']:
+        if not os.path.exists(cargo_toml_path):
+            print(f"Warning: {cargo_toml_path} not found. Skipping Cargo.toml update.")
+        else:
+            with open(cargo_toml_path, 'r', encoding='utf-8') as f:
+                cargo_content = f.read()
 
-        # [package] セクションの version = "..." を置換
-        # シンプルに正規表現で version 行を更新（コメント行は無視）
-        new_cargo_content = re.sub(
-            r'(?m)^version\s*=\s*["\']([^"\']+)["\']',
-            f'version = "{version}"',
-            cargo_content
-        )
+            # [package] セクションの version = "..." を置換
+            # シンプルに正規表現で version 行を更新（コメント行は無視）
+            new_cargo_content = re.sub(
+                r'(?m)^version\s*=\s*["\']([^"\']+)["\']',
+                f'version = "{version}"',
+                cargo_content
+            )
 
-        # 変更があった場合のみ上書き
-        if new_cargo_content != cargo_content:
-            with open(cargo_toml_path, 'w', encoding='utf-8') as f:
-                f.write(new_cargo_content)
-            print(f"Cargo.toml version updated to: {version}")
-        else:
-            print("Cargo.toml version already matches. No change.")
+            # 変更があった場合のみ上書き
+            if new_cargo_content != cargo_content:
+                with open(cargo_toml_path, 'w', encoding='utf-8') as f:
+                    f.write(new_cargo_content)
+                print(f"{cargo_toml_path} version updated to: {version}")
+            else:
+                print(f"{cargo_toml_path} version already matches. No change.")
 
     # 3. AppConfig.json のパス（既存のまま）
     config_path = 'src/assets/AppConfig.json'
@@ -0,0 +1,82 @@
+use cg_backup_core::error::{BackupError, ErrorKind, Result};
+use cg_backup_core::types::{BackupSettings, RetentionPolicy, StorageQuota};
+use cg_backup_core::utils;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// GUI と同じアプリ識別子 (tauri.conf.json の identifier)
+const APP_IDENTIFIER: &str = "com.m0090.dev.wbt";
+
+/// AppConfig.json のうち CLI が使う項目だけを読み込む
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct CliConfig {
+    auto_base_generation_threshold: f64,
+    bsdiff_max_file_size: u64,
+    retention: RetentionPolicy,
+    quota: StorageQuota,
+    restore_name_template: String,
+}
+
+impl Default for CliConfig {
+    fn default() -> Self {
+        let settings = BackupSettings::default();
+        Self {
+            auto_base_generation_threshold: settings.auto_base_generation_threshold,
+            bsdiff_max_file_size: settings.bsdiff_max_file_size,
+            retention: settings.retention,
+            quota: settings.quota,
+            restore_name_template: String::new(),
+        }
+    }
+}
+
+/// GUI が保存している AppConfig.json のパス (Tauri の app_config_dir と同じ場所)
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_IDENTIFIER).join("AppConfig.json"))
+}
+
+/// 設定ファイルから読み込んだ内容
+pub struct LoadedConfig {
+    /// エンジン用の設定
+    pub settings: BackupSettings,
+    /// 復元先のファイル名のテンプレート (GUI と共通、空なら既定の名前)
+    pub restore_name_template: String,
+}
+
+/// 設定ファイルからエンジン用の設定を組み立てる
+/// --config の指定がなければ GUI の設定を使い、それも無ければ既定値で動かす
+pub fn load_config(config: Option<&Path>) -> Result<LoadedConfig> {
+    let path = match config {
+        Some(p) => Some(p.to_path_buf()),
+        None => default_config_path().filter(|p| p.exists()),
+    };
+
+    let cfg = match path {
+        Some(path) => {
+            let data = fs::read_to_string(&path).map_err(|e| {
+                BackupError::new(ErrorKind::Config, "Failed to read config")
+                    .with_path(&path)
+                    .with_source(e)
+            })?;
+            serde_json::from_str::<CliConfig>(&data).map_err(|e| {
+                BackupError::new(ErrorKind::Config, "Invalid config")
+                    .with_path(&path)
+                    .with_source(e)
+            })?
+        }
+        None => CliConfig::default(),
+    };
+
+    Ok(LoadedConfig {
+        settings: BackupSettings {
+            auto_base_generation_threshold: cfg.auto_base_generation_threshold,
+            bsdiff_max_file_size: cfg.bsdiff_max_file_size,
+            hpatchz_path: utils::sidecar_path("hpatchz"),
+            retention: cfg.retention,
+            quota: cfg.quota,
+        },
+        restore_name_template: cfg.restore_name_template,
+    })
+}
@@ -0,0 +1,580 @@
+//! wbt: WorkBackupTool のコマンドライン版
+//!
+//! GUI と同じバックアップエンジン (cg-backup-core) を使い、
+//! レンダーファームのスクリプトや cron からバックアップ / 一覧 / 復元 / 検証を行う。
+mod config;
+
+use cg_backup_core::error::{BackupError, ErrorKind, Result, ResultExt};
+use cg_backup_core::progress::JobContext;
+use cg_backup_core::types::{
+    BackupItem, BackupOutcome, BackupRequest, BackupSettings, RestoreOptions, VerifyStatus,
+};
+use cg_backup_core::watch::{FileWatcher, WatchOptions};
+use cg_backup_core::{backup, delete, history, pin, prune, restore, utils, verify};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "wbt", version, about = "WorkBackupTool command line interface")]
+struct Cli {
+    /// 結果を JSON で出力する
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// AppConfig.json のパス (省略時は GUI の設定を使う)
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 作業ファイルをバックアップする
+    Backup {
+        #[command(flatten)]
+        target: Target,
+
+        #[command(flatten)]
+        options: BackupOptions,
+    },
+    /// 作業ファイルの保存を監視し、保存が落ち着くたびにバックアップする (Ctrl+C で終了)
+    Watch {
+        #[command(flatten)]
+        target: Target,
+
+        #[command(flatten)]
+        options: BackupOptions,
+
+        /// 保存完了とみなすまでの待ち時間 (秒)
+        #[arg(long, default_value_t = 3)]
+        settle_secs: u64,
+    },
+    /// バックアップ履歴を一覧する
+    List {
+        #[command(flatten)]
+        target: Target,
+    },
+    /// 共有のバックアップ先フォルダの履歴を、元の作業ファイルごとに一覧する
+    ListAll {
+        /// バックアップ先フォルダ
+        backup_dir: String,
+    },
+    /// バックアップを作業ファイルの隣に別名で復元する
+    Restore {
+        #[command(flatten)]
+        target: Target,
+
+        /// 復元するバックアップ (バックアップ ID、list で表示されるファイル名、またはパス)
+        id: String,
+
+        /// 別名で保存せず、作業ファイルを直接置き換える
+        /// (置き換える前の作業ファイルはフルコピーとしてバックアップされる)
+        #[arg(long, conflicts_with_all = ["output", "output_dir", "name_template"])]
+        in_place: bool,
+
+        /// 復元先のファイルパス
+        #[arg(long, default_value = "", conflicts_with_all = ["output_dir", "name_template"])]
+        output: String,
+
+        /// 復元先のフォルダ (省略時は作業ファイルと同じフォルダ)
+        #[arg(long, default_value = "")]
+        output_dir: String,
+
+        /// 復元先のファイル名のテンプレート ({stem} {ext} {name} {id} {backupId}、
+        /// 省略時は AppConfig の restoreNameTemplate、それも無ければ "{stem}_restored_{id}{ext}")
+        #[arg(long, default_value = "")]
+        name_template: String,
+    },
+    /// バックアップ履歴が復元可能か検証する
+    Verify {
+        #[command(flatten)]
+        target: Target,
+    },
+    /// バックアップをマイルストーンとしてピン留めする (prune で削除されなくなる)
+    Pin {
+        #[command(flatten)]
+        target: Target,
+
+        /// 対象のバックアップ (バックアップ ID、list で表示されるファイル名、またはパス)
+        id: String,
+
+        /// マイルストーン名 ("sent to client" など)
+        #[arg(long, default_value = "")]
+        label: String,
+
+        /// ピン留めを解除する
+        #[arg(long)]
+        unpin: bool,
+    },
+    /// バックアップ 1 件を削除する (.note も削除)
+    Delete {
+        #[command(flatten)]
+        target: Target,
+
+        /// 削除するバックアップ (バックアップ ID、list で表示されるファイル名、またはパス)
+        id: String,
+
+        /// .base を指定した場合、それを使う差分もまとめて削除する
+        #[arg(long)]
+        cascade: bool,
+    },
+    /// 世代フォルダ (baseN_...) から作業ファイルの .base と差分を削除する
+    /// (他の作業ファイルのバックアップは残し、フォルダは空になったときだけ消す)
+    DeleteGeneration {
+        #[command(flatten)]
+        target: Target,
+
+        /// 世代フォルダ名 (base1_20260101_120000 など)、またはパス
+        dir: String,
+    },
+    /// 保持ルール (AppConfig の retention) に従って古いバックアップを削除する
+    Prune {
+        #[command(flatten)]
+        target: Target,
+
+        /// 削除せずに、削除対象だけを表示する
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// 各サブコマンド共通の対象指定
+#[derive(Args)]
+struct Target {
+    /// 作業ファイル
+    work_file: String,
+
+    /// バックアップ先フォルダ (省略時は作業ファイルの隣の cg_backup_*)
+    #[arg(long, default_value = "")]
+    backup_dir: String,
+}
+
+/// backup / watch 共通のバックアップ方式の指定
+#[derive(Args)]
+struct BackupOptions {
+    /// バックアップ方式
+    #[arg(long, value_enum, default_value_t = Mode::Diff)]
+    mode: Mode,
+
+    /// 差分アルゴリズム (diff モード)
+    #[arg(long, default_value = "hdiff", value_parser = ["hdiff", "bsdiff"])]
+    algo: String,
+
+    /// hdiff の圧縮方式 (diff モード)
+    #[arg(long, default_value = "zstd")]
+    compress: String,
+
+    /// アーカイブ形式 (archive モード)
+    #[arg(long, default_value = "zip", value_parser = ["zip", "tar"])]
+    format: String,
+
+    /// ZIP のパスワード (archive モード)
+    #[arg(long, default_value = "")]
+    password: String,
+}
+
+impl BackupOptions {
+    fn request(&self, target: &Target) -> BackupRequest {
+        BackupRequest {
+            work_file: target.work_file.clone(),
+            backup_dir: target.backup_dir.clone(),
+            mode: self.mode.as_str().to_string(),
+            algo: self.algo.clone(),
+            compress: self.compress.clone(),
+            format: self.format.clone(),
+            password: self.password.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Mode {
+    Diff,
+    Copy,
+    Archive,
+}
+
+impl Mode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Mode::Diff => "diff",
+            Mode::Copy => "copy",
+            Mode::Archive => "archive",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupOutput {
+    mode: &'static str,
+    #[serde(flatten)]
+    outcome: BackupOutcome,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreOutput {
+    source: String,
+    restored: String,
+    /// --in-place で置き換える前の作業ファイルのバックアップ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot: Option<BackupOutcome>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PinOutput {
+    path: String,
+    pinned: bool,
+}
+
+#[derive(Serialize)]
+struct ErrorOutput<'a> {
+    error: &'a BackupError,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(&cli) {
+        Ok(code) => code,
+        Err(e) => {
+            print_error(cli.json, &e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<ExitCode> {
+    let config::LoadedConfig {
+        settings,
+        restore_name_template,
+    } = config::load_config(cli.config.as_deref())?;
+
+    match &cli.command {
+        Command::Backup { target, options } => {
+            let outcome =
+                backup::run_backup(&options.request(target), &settings, &JobContext::default())?;
+            print_backup(cli, options.mode, outcome);
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Watch {
+            target,
+            options,
+            settle_secs,
+        } => {
+            fs::metadata(&target.work_file).at(&target.work_file)?;
+            let req = options.request(target);
+            let mode = options.mode;
+            let json = cli.json;
+            let watch_options = WatchOptions {
+                settle_time: Duration::from_secs(*settle_secs),
+                ..WatchOptions::default()
+            };
+            if !json {
+                println!("Watching {} (Ctrl+C to stop)", target.work_file);
+            }
+
+            let _watcher = FileWatcher::spawn(&target.work_file, watch_options, move |_| {
+                // 失敗しても監視は続ける
+                match backup::run_backup(&req, &settings, &JobContext::default()) {
+                    Ok(outcome) => print_backup_to(json, mode, outcome),
+                    Err(e) => print_error(json, &e),
+                }
+            });
+            loop {
+                std::thread::park();
+            }
+        }
+        Command::List { target } => {
+            let list = history::get_backup_list(&target.work_file, &target.backup_dir)?;
+            if cli.json {
+                print_json(&list);
+            } else {
+                print_list(&list);
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::ListAll { backup_dir } => {
+            let groups = history::get_backups_by_work_file(backup_dir)?;
+            if cli.json {
+                print_json(&groups);
+            } else {
+                for (i, (work_file, list)) in groups.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    println!("{} ({} backups)", work_file, list.len());
+                    print_list(list);
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Restore {
+            target,
+            id,
+            in_place,
+            output,
+            output_dir,
+            name_template,
+        } => {
+            let source = find_backup(target, id)?;
+            let job = JobContext::default();
+            let output = if *in_place {
+                let result = restore::restore_in_place(
+                    &source,
+                    &target.work_file,
+                    &target.backup_dir,
+                    &settings,
+                    &job,
+                )?;
+                RestoreOutput {
+                    source: result.source,
+                    restored: result.work_file,
+                    snapshot: result.snapshot,
+                }
+            } else {
+                let options = RestoreOptions {
+                    output_path: output.clone(),
+                    output_dir: output_dir.clone(),
+                    name_template: if name_template.is_empty() {
+                        restore_name_template
+                    } else {
+                        name_template.clone()
+                    },
+                };
+                let restored =
+                    restore::restore_backup(&source, &target.work_file, &options, &settings, &job)?;
+                RestoreOutput {
+                    source,
+                    restored,
+                    snapshot: None,
+                }
+            };
+
+            if cli.json {
+                print_json(&output);
+            } else {
+                println!("Restored {} -> {}", output.source, output.restored);
+                if let Some(snapshot) = &output.snapshot {
+                    println!("Previous work file saved as {}", snapshot.path());
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Verify { target } => run_verify(cli, target, &settings),
+        Command::Pin {
+            target,
+            id,
+            label,
+            unpin,
+        } => {
+            let path = find_backup(target, id)?;
+            pin::set_pinned(&path, !unpin, label)?;
+
+            let output = PinOutput {
+                path,
+                pinned: !unpin,
+            };
+            if cli.json {
+                print_json(&output);
+            } else {
+                let action = if output.pinned { "Pinned" } else { "Unpinned" };
+                println!("{} {}", action, output.path);
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Delete {
+            target,
+            id,
+            cascade,
+        } => {
+            // .base は list に出ないので、パスで直接指定されたものも受け付ける
+            let path = if Path::new(id).is_file() {
+                id.clone()
+            } else {
+                find_backup(target, id)?
+            };
+            let deleted = delete::delete_backup(&path, *cascade)?;
+            print_deleted(cli, &deleted);
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::DeleteGeneration { target, dir } => {
+            let path = if Path::new(dir).is_dir() {
+                PathBuf::from(dir)
+            } else {
+                backup_root(target).join(dir)
+            };
+            let deleted = delete::delete_generation(&target.work_file, &path.to_string_lossy())?;
+            print_deleted(cli, &deleted);
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Prune { target, dry_run } => {
+            let report = prune::prune_backups(
+                &target.work_file,
+                &target.backup_dir,
+                &settings.retention,
+                *dry_run,
+            )?;
+            if cli.json {
+                print_json(&report);
+            } else {
+                let label = if report.dry_run {
+                    "Would delete"
+                } else {
+                    "Deleted"
+                };
+                for path in &report.deleted {
+                    println!("{}  {}", label, path);
+                }
+                println!(
+                    "{} files ({} bytes), {} backups kept",
+                    report.deleted.len(),
+                    report.freed_bytes,
+                    report.kept
+                );
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+fn run_verify(cli: &Cli, target: &Target, settings: &BackupSettings) -> Result<ExitCode> {
+    let results = verify::verify_backups(&target.work_file, &target.backup_dir, settings)?;
+    let failed = results.iter().filter(|r| !r.ok).count();
+
+    if cli.json {
+        print_json(&results);
+    } else {
+        for r in &results {
+            if r.ok {
+                println!("OK        {}", r.file_name);
+            } else {
+                println!(
+                    "{:<8}  {}: {}",
+                    status_label(r.status),
+                    r.file_name,
+                    r.message
+                );
+            }
+        }
+        println!("{} checked, {} failed", results.len(), failed);
+    }
+
+    // 壊れたバックアップがあればスクリプト側で検知できるよう失敗扱いにする
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn status_label(status: VerifyStatus) -> &'static str {
+    match status {
+        VerifyStatus::Ok => "OK",
+        VerifyStatus::Corrupt => "CORRUPT",
+        VerifyStatus::Orphaned => "ORPHANED",
+        VerifyStatus::Missing => "MISSING",
+        VerifyStatus::Error => "FAILED",
+    }
+}
+
+/// list で表示したファイル名 (またはパス) から復元対象を特定する
+fn find_backup(target: &Target, id: &str) -> Result<String> {
+    let list = history::get_backup_list(&target.work_file, &target.backup_dir)?;
+
+    list.into_iter()
+        .find(|item| {
+            item.id == id || item.file_name == id || Path::new(&item.file_path) == Path::new(id)
+        })
+        .map(|item| item.file_path)
+        .ok_or_else(|| BackupError::new(ErrorKind::NotFound, "Backup not found").with_path(id))
+}
+
+fn backup_root(target: &Target) -> PathBuf {
+    if target.backup_dir.is_empty() {
+        utils::default_backup_dir(&target.work_file)
+    } else {
+        PathBuf::from(&target.backup_dir)
+    }
+}
+
+fn print_deleted(cli: &Cli, deleted: &[String]) {
+    if cli.json {
+        print_json(deleted);
+    } else {
+        for path in deleted {
+            println!("Deleted  {}", path);
+        }
+    }
+}
+
+fn print_list(list: &[BackupItem]) {
+    for item in list {
+        let generation = if item.generation > 0 {
+            format!("base{}", item.generation)
+        } else {
+            "-".to_string()
+        };
+        let pin = if item.pinned {
+            format!("  [pinned] {}", item.pin_label)
+        } else {
+            String::new()
+        };
+        println!(
+            "{:<19}  {:>12}  {:<6}  {}{}",
+            item.timestamp,
+            item.file_size,
+            generation,
+            item.file_name,
+            pin.trim_end()
+        );
+    }
+}
+
+fn print_backup(cli: &Cli, mode: Mode, outcome: BackupOutcome) {
+    print_backup_to(cli.json, mode, outcome)
+}
+
+fn print_backup_to(json: bool, mode: Mode, outcome: BackupOutcome) {
+    let output = BackupOutput {
+        mode: mode.as_str(),
+        outcome,
+    };
+    if json {
+        print_json(&output);
+    } else if output.outcome.is_unchanged() {
+        println!(
+            "No changes since the last backup: {}",
+            output.outcome.path()
+        );
+    } else {
+        println!(
+            "Backup created ({}): {}",
+            output.mode,
+            output.outcome.path()
+        );
+    }
+}
+
+fn print_error(json: bool, e: &BackupError) {
+    if json {
+        print_json(&ErrorOutput { error: e });
+    } else {
+        eprintln!("Error: {}", e);
+    }
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(s) => println!("{}", s),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
@@ -0,0 +1,155 @@
+use crate::error::{BackupError, Result, ResultExt};
+use crate::utils;
+use regex::Regex;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// 書き込み中の一時ファイルに付ける拡張子 (履歴の一覧には出ない)
+pub const TEMP_EXT: &str = "wbt-tmp";
+
+/// これより古い一時ファイルは、書き込み中のジョブのものではないとみなして消す
+pub const STALE_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// final_path と同じフォルダに置く一時ファイルのパス (".名前.ID.wbt-tmp")
+/// 同じフォルダに置くことで、確定時の rename が別ドライブへのコピーにならない
+pub fn temp_path(final_path: &Path) -> PathBuf {
+    let name = final_path
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    final_path.with_file_name(format!(".{}.{}.{}", name, utils::new_backup_id(), TEMP_EXT))
+}
+
+/// 書き終えた一時ファイルを fsync してから final_path に rename し、フォルダの更新もディスクに確定させる
+/// (外部プロセス (hpatchz) が書いたファイルもここで確定させる)
+pub fn commit(temp: &Path, final_path: &Path) -> Result<()> {
+    // Windows の FlushFileBuffers は書き込み権限が必要なため write で開く
+    OpenOptions::new()
+        .write(true)
+        .open(temp)
+        .and_then(|f| f.sync_all())
+        .at(temp)
+        .context("Failed to sync file")?;
+    fs::rename(temp, final_path)
+        .at(final_path)
+        .context("Failed to finalize file")?;
+    // "name.ext" のような相対パスの親は "" になるため、カレントフォルダとして扱う
+    match final_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => sync_dir(dir),
+        Some(_) => sync_dir(Path::new(".")),
+        None => Ok(()),
+    }
+}
+
+/// 一時ファイルに write で書き込み、commit で final_path に置き換える
+/// write は受け取ったファイルを書き終えたら返す (ZipWriter::finish などの戻り値をそのまま返せる)
+/// 失敗・中断した場合は一時ファイルを消し、final_path には何も残さない (既存のファイルも壊さない)
+pub fn write_file<F>(final_path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(File) -> Result<File>,
+{
+    if let Some(parent) = final_path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            fs::create_dir_all(parent)
+                .at(parent)
+                .context("Failed to create directory")?;
+        }
+    }
+
+    let temp = temp_path(final_path);
+    let result = File::create(&temp)
+        .at(&temp)
+        .context("Failed to create temporary file")
+        .and_then(write)
+        .and_then(|file| {
+            drop(file);
+            commit(&temp, final_path)
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// フォルダのエントリ (rename の結果) をディスクに確定させる
+/// Windows ではフォルダを開けないため何もしない (NTFS はメタデータをジャーナルで守る)
+pub fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        File::open(dir)
+            .and_then(|d| d.sync_all())
+            .at(dir)
+            .context("Failed to sync directory")?;
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// 書きかけのまま残った一時ファイルを消し、消したファイルを返す
+/// dir 直下と、その 1 階層下 (世代フォルダ) を対象にする
+/// 実行中のジョブのファイルを消さないよう、STALE_AGE より古いものだけを消す
+pub fn sweep_stale(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    if !dir.is_dir() {
+        return Ok(removed);
+    }
+    for entry in fs::read_dir(dir).at(dir)?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if let Ok(entries) = fs::read_dir(&path) {
+                for sub in entries.flatten() {
+                    remove_if_stale(&sub.path(), is_temp_name, &mut removed);
+                }
+            }
+        } else {
+            remove_if_stale(&path, is_temp_name, &mut removed);
+        }
+    }
+    Ok(removed)
+}
+
+/// 旧版が std::env::temp_dir() に作っていた差分の一時ファイル ("名前.日時.tmp") を消す
+pub fn sweep_legacy_temp_dir() -> Result<Vec<PathBuf>> {
+    let dir = std::env::temp_dir();
+    let pattern = Regex::new(r"\.\d{8}_\d{6}(?:_\d{6})?\.tmp$").map_err(BackupError::internal)?;
+    let mut removed = Vec::new();
+    for entry in fs::read_dir(&dir).at(&dir)?.flatten() {
+        remove_if_stale(&entry.path(), |n| pattern.is_match(n), &mut removed);
+    }
+    Ok(removed)
+}
+
+fn is_temp_name(name: &str) -> bool {
+    name.starts_with('.') && name.ends_with(&format!(".{}", TEMP_EXT))
+}
+
+fn remove_if_stale(path: &Path, matches: impl Fn(&str) -> bool, removed: &mut Vec<PathBuf>) {
+    let name = match path.file_name().and_then(|s| s.to_str()) {
+        Some(n) => n,
+        None => return,
+    };
+    if !matches(name) {
+        return;
+    }
+    let meta = match fs::metadata(path) {
+        Ok(m) if m.is_file() => m,
+        _ => return,
+    };
+    let age = meta
+        .modified()
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .unwrap_or_default();
+    if age < STALE_AGE {
+        return;
+    }
+    match fs::remove_file(path) {
+        Ok(()) => {
+            log::debug!("Removed stale temporary file: {:?}", path);
+            removed.push(path.to_path_buf());
+        }
+        Err(e) => log::warn!("Failed to remove stale temporary file {:?}: {}", path, e),
+    }
+}
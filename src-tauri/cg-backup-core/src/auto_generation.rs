@@ -1,30 +1,34 @@
+use crate::checksum;
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::manifest;
+use crate::progress::JobContext;
+use crate::types::BackupGenInfo;
+use crate::utils;
 use regex::Regex;
 use std::fs;
 use std::path::{Path, PathBuf};
-//use crate::app::types::R;
-use crate::app::types::BackupGenInfo;
 
 /// 最新の baseN_... フォルダを特定する
 /// Go版の FindLatestBaseDir / GetLatestGeneration とロジックを完全同期
-pub fn get_latest_generation(root: &Path) -> Result<Option<BackupGenInfo>, String> {
+pub fn get_latest_generation(root: &Path) -> Result<Option<BackupGenInfo>> {
     if !root.exists() {
         return Ok(None);
     }
 
-    let entries = fs::read_dir(root).map_err(|e| e.to_string())?;
+    let entries = fs::read_dir(root).at(root)?;
     // Go版の ^base(\d+)_ に合わせる。アンダースコア以降があるもののみ対象
-    let re = Regex::new(r"^base(\d+)_").unwrap();
+    let re = Regex::new(r"^base\d+_").unwrap();
 
     let mut latest_idx = -1;
     let mut latest_dir_name: Option<String> = None;
 
     for entry in entries {
-        let entry = entry.map_err(|e| e.to_string())?;
-        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+        let entry = entry.at(root)?;
+        if entry.file_type().at(entry.path())?.is_dir() {
             let name = entry.file_name().to_string_lossy().into_owned();
-            if let Some(caps) = re.captures(&name) {
-                // indexを取得
-                if let Ok(idx) = caps[1].parse::<i32>() {
+            if re.is_match(&name) {
+                // indexを取得 (manifest.json があればその世代番号、無ければフォルダ名から)
+                if let Some(idx) = manifest::generation_index(&entry.path()) {
                     // Go版の `if idx >= maxIdx` を再現。
                     // idxが同じなら、文字列比較（タイムスタンプが新しい方）を優先
                     if idx > latest_idx
@@ -50,7 +54,7 @@ pub fn get_latest_generation(root: &Path) -> Result<Option<BackupGenInfo>, Strin
 
 /// 最新の世代フォルダを取得（なければ作成）
 /// Go版 ResolveGenerationDir と同じく、存在しない場合は index 1 で作成する
-pub fn resolve_generation_dir(root: &Path, work_file: &str) -> Result<(PathBuf, i32), String> {
+pub fn resolve_generation_dir(root: &Path, work_file: &str) -> Result<(PathBuf, i32)> {
     match get_latest_generation(root)? {
         Some(info) => Ok((info.dir_path, info.base_idx)),
         None => {
@@ -63,25 +67,34 @@ pub fn resolve_generation_dir(root: &Path, work_file: &str) -> Result<(PathBuf,
 
 /// 新しい世代フォルダを作成し、.base をコピーする
 /// Go版 CreateNewGeneration に相当
-pub fn create_new_generation(root: &Path, idx: i32, work_file: &str) -> Result<PathBuf, String> {
-    let ts = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
+pub fn create_new_generation(root: &Path, idx: i32, work_file: &str) -> Result<PathBuf> {
+    let ts = utils::new_backup_id();
     let new_dir_name = format!("base{}_{}", idx, ts);
     let new_dir_path = root.join(new_dir_name);
 
     // フォルダ作成 (mkdir -p)
-    fs::create_dir_all(&new_dir_path).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&new_dir_path).at(&new_dir_path)?;
 
     // .base ファイルのコピー先パス
     let file_path = Path::new(work_file);
     let file_name = file_path
         .file_name()
-        .ok_or_else(|| "Invalid work file name".to_string())?
+        .ok_or_else(|| {
+            BackupError::new(ErrorKind::InvalidInput, "Invalid work file name").with_path(work_file)
+        })?
         .to_string_lossy();
 
     let base_path = new_dir_path.join(format!("{}.base", file_name));
 
     // 実ファイルのコピー (CopyFile相当)
-    fs::copy(work_file, &base_path).map_err(|e| format!("Failed to copy base file: {}", e))?;
+    utils::copy_file(
+        work_file,
+        &base_path.to_string_lossy(),
+        &JobContext::default(),
+    )
+    .context("Failed to copy base file")?;
+    checksum::record_file(&new_dir_path, &base_path, None)?;
+    manifest::record_base(&new_dir_path, &base_path, work_file)?;
 
     Ok(new_dir_path)
 }
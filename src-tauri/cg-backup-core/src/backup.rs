@@ -0,0 +1,590 @@
+use crate::atomic;
+use crate::auto_generation;
+use crate::checksum;
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::hdiff_common::{compressor_name, create_diff};
+use crate::index;
+use crate::manifest::{self, ManifestDiff};
+use crate::progress::{JobContext, Phase};
+use crate::space;
+use crate::types::{BackupOutcome, BackupRequest, BackupSettings};
+use crate::utils;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 指定されたモードでバックアップを作成する
+/// (手動実行・自動バックアップのどちらもここを通る)
+/// 作業ファイルが前回のバックアップから変わっていなければ、作成せずに Unchanged を返す
+/// 進捗は job に通知し、job が中断されたら書きかけのファイルを消して Cancelled を返す
+pub fn run_backup(
+    req: &BackupRequest,
+    settings: &BackupSettings,
+    job: &JobContext,
+) -> Result<BackupOutcome> {
+    // 未指定の項目は GUI の既定値に合わせる
+    fn or_default<'a>(v: &'a str, default: &'a str) -> &'a str {
+        if v.is_empty() {
+            default
+        } else {
+            v
+        }
+    }
+
+    let outcome = match req.mode.as_str() {
+        "" | "diff" => backup_or_diff(
+            &req.work_file,
+            &req.backup_dir,
+            or_default(&req.algo, "hdiff"),
+            or_default(&req.compress, "zstd"),
+            settings,
+            job,
+        ),
+        "copy" => copy_backup_file(&req.work_file, &req.backup_dir, settings, job),
+        // アーカイブは圧縮後の中身を比べられないため、毎回作成する
+        "archive" => archive_backup_file(
+            &req.work_file,
+            &req.backup_dir,
+            or_default(&req.format, "zip"),
+            &req.password,
+            settings,
+            job,
+        )
+        .map(|path| BackupOutcome::Created { path }),
+        other => Err(BackupError::new(
+            ErrorKind::InvalidInput,
+            format!("Unknown backup mode: {}", other),
+        )),
+    }?;
+
+    // 履歴の一覧がすぐに新しいバックアップを返せるよう、索引を更新しておく
+    if let BackupOutcome::Created { path } = &outcome {
+        index::refresh(Path::new(path));
+    }
+    Ok(outcome)
+}
+
+/// 差分バックアップを作成する
+/// custom_dir が世代フォルダ (baseN_...) ならそこへ、親フォルダなら最新世代へ保存し、
+/// 差分が閾値を超えた場合は世代交代する
+/// 保存先の最新の差分と作業ファイルが同じ内容なら、差分を作らずに Unchanged を返す
+pub fn backup_or_diff(
+    work_file: &str,
+    custom_dir: &str,
+    algo: &str,
+    compress: &str,
+    settings: &BackupSettings,
+    job: &JobContext,
+) -> Result<BackupOutcome> {
+    // --- 0. 作業ファイルの確認 ---
+    // 存在しない場合、世代フォルダを作る前にエラーにする
+    let work_meta = fs::metadata(work_file).at(work_file)?;
+
+    // bsdiff は新旧ファイルを丸ごとメモリに載せるため、大きなファイルは受け付けない
+    if algo == "bsdiff" {
+        let max_size = settings.bsdiff_max_file_size;
+        let size = work_meta.len();
+        if max_size > 0 && size > max_size {
+            return Err(BackupError::new(
+                ErrorKind::TooLarge,
+                format!(
+                    "File is too large for bsdiff ({} bytes > {} bytes). Please use hdiff instead.",
+                    size, max_size
+                ),
+            )
+            .with_path(work_file));
+        }
+    }
+
+    // --- 1. ディレクトリの決定 ---
+    // Linux/WSL2での末尾スラッシュ問題を避けるため、一旦trimしてPathBufを作成
+    let initial_path = if custom_dir.is_empty() {
+        utils::default_backup_dir(work_file)
+    } else {
+        PathBuf::from(custom_dir.trim_end_matches(['/', '\\']))
+    };
+
+    let target_dir: PathBuf;
+    let mut current_idx: i32 = 0;
+    let project_root: PathBuf;
+
+    // 確実に「最後のフォルダ名」を取得するための堅牢な方法
+    let folder_name = initial_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("");
+
+    log::debug!(
+        "STEP1 - initial_path={:?}, folder_name='{}'",
+        initial_path,
+        folder_name
+    );
+
+    // --- 1a. 手動選択された世代フォルダか、親フォルダかの判定 ---
+    if folder_name.starts_with("base") {
+        log::debug!("STEP2 - Entering MANUAL route");
+        // A. 特定の世代フォルダ (.../baseN) が直接指定されている場合
+        target_dir = initial_path.clone();
+
+        // 親ディレクトリを「世代交代の起点」として保持する
+        project_root = initial_path
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_else(|| initial_path.clone());
+
+        // Regexでインデックスを抽出
+        let re_idx = Regex::new(r"base(\d+)").unwrap();
+        if let Some(caps) = re_idx.captures(folder_name) {
+            current_idx = caps[1].parse().unwrap_or(0);
+        }
+        log::debug!("Manual Index identified as {}", current_idx);
+    } else {
+        log::debug!(
+            "STEP2 - Entering AUTO route (via parent dir: '{}')",
+            folder_name
+        );
+        // B. 親フォルダが指定されている場合
+        project_root = initial_path.clone();
+        let (resolved_path, idx) =
+            auto_generation::resolve_generation_dir(&project_root, work_file)?;
+        target_dir = resolved_path;
+        current_idx = idx;
+        log::debug!("Auto Resolved Path={:?}, idx={}", target_dir, current_idx);
+    }
+
+    // フォルダの存在保証
+    if !target_dir.exists() {
+        fs::create_dir_all(&target_dir).at(&target_dir)?;
+    }
+
+    let file_name = Path::new(work_file)
+        .file_name()
+        .ok_or_else(|| {
+            BackupError::new(ErrorKind::InvalidInput, "Invalid work file name").with_path(work_file)
+        })?
+        .to_string_lossy();
+    let base_full = target_dir.join(format!("{}.base", file_name));
+
+    // 前回の差分と比べるための現在のハッシュ
+    // (記録には、差分エンジンが実際に読んだ内容のハッシュを使う。読む間に保存されても食い違わない)
+    let work_hash = checksum::hash_file(work_file)?;
+
+    // --- 1b. 変更の確認 ---
+    // 前回の差分と同じ内容なら、同じ履歴を増やさない
+    if let Some((last_diff, last_hash)) = checksum::latest_diff(&target_dir, &file_name)? {
+        if last_hash == work_hash {
+            log::debug!("Work file unchanged since {:?}", last_diff);
+            return Ok(BackupOutcome::Unchanged {
+                path: last_diff.to_string_lossy().into_owned(),
+            });
+        }
+    }
+
+    // --- 1c. 容量の確認 ---
+    // 書き込みを始める前に quota と空き容量を確かめ、足りなければすぐにエラーにする
+    let expected = space::estimate_diff_size(&target_dir, &file_name, work_meta.len())?;
+    space::preflight(work_file, &project_root, &target_dir, expected, settings)?;
+
+    // --- 2. .baseファイルの同期 ---
+    if !base_full.exists() {
+        utils::copy_file(work_file, &base_full.to_string_lossy(), job)
+            .context("Failed to sync base file")?;
+        checksum::record_file(&target_dir, &base_full, None)?;
+        manifest::record_base(&target_dir, &base_full, work_file)?;
+    }
+
+    // 差分は確定先と同じフォルダの一時ファイルに作り、rename で確定させる
+    let ts = utils::new_backup_id();
+    let final_path = target_dir.join(format!("{}.{}.{}.diff", file_name, ts, algo));
+    let temp_diff = atomic::temp_path(&final_path);
+
+    // --- 3. 差分生成 (hdiff / bsdiff) ---
+    // 失敗・中断した場合、書きかけの差分は create_diff が消す (hdiff / bsdiff とも)
+    let diff_source = create_diff(
+        algo,
+        &base_full.to_string_lossy(),
+        work_file,
+        &temp_diff.to_string_lossy(),
+        compress,
+        job,
+    )?;
+
+    // --- 4. サイズ・閾値判定 ---
+    let work_size = diff_source.size;
+    let diff_size = fs::metadata(&temp_diff).at(&temp_diff)?.len();
+
+    let threshold = {
+        let t = settings.auto_base_generation_threshold;
+        if t <= 0.0 {
+            0.8
+        } else {
+            t
+        }
+    };
+
+    log::debug!(
+        "work_size={}, diff_size={}, threshold={}, current_idx={}",
+        work_size,
+        diff_size,
+        threshold,
+        current_idx
+    );
+
+    let mut should_next_gen = false;
+    if work_size > 100 * 1024 && (diff_size as f64) > (work_size as f64) * threshold {
+        should_next_gen = true;
+        log::debug!("Threshold exceeded. Rotation triggered.");
+    }
+
+    if should_next_gen {
+        // --- 5a. 【世代交代】 ここを新しいロジックに差し替えます ---
+        let _ = fs::remove_file(&temp_diff);
+
+        // 新しい世代には .base (作業ファイルのコピー) と差分を書き込む
+        space::preflight(
+            work_file,
+            &project_root,
+            &project_root,
+            work_size.saturating_add(diff_size),
+            settings,
+        )?;
+
+        // ★修正：既存の最新世代があるか再確認
+        let (new_gen_dir, _) = match auto_generation::get_latest_generation(&project_root)? {
+            Some(info) if info.base_idx > current_idx => {
+                log::debug!(
+                    "Existing newer generation found (idx {}). Using it.",
+                    info.base_idx
+                );
+                (info.dir_path, info.base_idx)
+            }
+            _ => {
+                let next_idx = current_idx + 1;
+                log::debug!("Creating next generation: idx {}", next_idx);
+                let path =
+                    auto_generation::create_new_generation(&project_root, next_idx, work_file)?;
+                (path, next_idx)
+            }
+        };
+
+        // 以降、決定した new_gen_dir を使って diff を作成
+        let new_base_full = new_gen_dir.join(format!("{}.base", file_name));
+        let final_path = new_gen_dir.join(format!("{}.{}.{}.diff", file_name, ts, algo));
+
+        // 念のため、既存フォルダを使う場合に .base が無いならコピーする（より安全にする場合）
+        if !new_base_full.exists() {
+            utils::copy_file(work_file, &new_base_full.to_string_lossy(), job)?;
+            checksum::record_file(&new_gen_dir, &new_base_full, None)?;
+            manifest::record_base(&new_gen_dir, &new_base_full, work_file)?;
+        }
+
+        let temp_diff = atomic::temp_path(&final_path);
+        let diff_source = create_diff(
+            algo,
+            &new_base_full.to_string_lossy(),
+            work_file,
+            &temp_diff.to_string_lossy(),
+            compress,
+            job,
+        )?;
+        // 確定前に中断されたら一時ファイルごと捨てる (5b と同じ)
+        let result = job.check().and_then(|()| {
+            job.report(Phase::Finalizing, work_size, work_size);
+            atomic::commit(&temp_diff, &final_path)
+        });
+        if let Err(e) = result {
+            let _ = fs::remove_file(&temp_diff);
+            return Err(e);
+        }
+        record_diff(
+            &new_gen_dir,
+            &final_path,
+            &new_base_full,
+            diff_source,
+            &ts,
+            algo,
+            compress,
+        )?;
+        Ok(BackupOutcome::Created {
+            path: final_path.to_string_lossy().into_owned(),
+        })
+    } else {
+        // --- 5b. 【維持】 現在のフォルダ内に diff を確定 ---
+        // 確定前に中断されたら一時ファイルごと捨てる
+        let result = job.check().and_then(|()| {
+            job.report(Phase::Finalizing, work_size, work_size);
+            atomic::commit(&temp_diff, &final_path)
+        });
+        if let Err(e) = result {
+            let _ = fs::remove_file(&temp_diff);
+            return Err(e);
+        }
+
+        record_diff(
+            &target_dir,
+            &final_path,
+            &base_full,
+            diff_source,
+            &ts,
+            algo,
+            compress,
+        )?;
+        Ok(BackupOutcome::Created {
+            path: final_path.to_string_lossy().into_owned(),
+        })
+    }
+}
+
+/// 確定した差分を checksum.json (ハッシュ) と manifest.json (説明) に記録する
+fn record_diff(
+    dir: &Path,
+    diff_path: &Path,
+    base_path: &Path,
+    source: checksum::FileHash,
+    id: &str,
+    algo: &str,
+    compress: &str,
+) -> Result<()> {
+    let source_size = source.size;
+    checksum::record_diff(dir, diff_path, source, base_path)?;
+    manifest::record_diff(
+        dir,
+        ManifestDiff {
+            file_name: file_name_of(diff_path),
+            id: id.to_string(),
+            created_at: manifest::now(),
+            base: file_name_of(base_path),
+            algo: algo.to_string(),
+            compressor: compressor_name(algo, compress).to_string(),
+            size: fs::metadata(diff_path).at(diff_path)?.len(),
+            source_size,
+            note: String::new(),
+            app_version: manifest::APP_VERSION.to_string(),
+        },
+    )
+}
+
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// ファイルをそのままコピーしてバックアップする (Go版の CopyBackupFile 相当)
+/// 最新のコピーと同じ内容なら、コピーせずに Unchanged を返す
+pub fn copy_backup_file(
+    src: &str,
+    backup_dir: &str,
+    settings: &BackupSettings,
+    job: &JobContext,
+) -> Result<BackupOutcome> {
+    // 1. バックアップ先ディレクトリの決定
+    // backup_dir が空ならソースファイルに基づいたデフォルトディレクトリを作成
+    let target_dir = if backup_dir.is_empty() {
+        utils::default_backup_dir(src)
+    } else {
+        PathBuf::from(backup_dir)
+    };
+
+    // 2. ディレクトリの作成 (MkdirAll 0755 相当)
+    // utils::copy_file 内部でも作成していますが、Go版の構造に合わせここで明示的に作成
+    if !target_dir.exists() {
+        fs::create_dir_all(&target_dir)
+            .at(&target_dir)
+            .context("Failed to create backup folder")?;
+    }
+
+    // 3. 変更の確認 (サイズが同じときだけハッシュを比べる)
+    let src_size = fs::metadata(src).at(src)?.len();
+    if let Some(last) = latest_copy(&target_dir, src)? {
+        let last_size = fs::metadata(&last).at(&last)?.len();
+        if src_size == last_size && checksum::hash_file(src)? == checksum::hash_file(&last)? {
+            log::debug!("Work file unchanged since {:?}", last);
+            return Ok(BackupOutcome::Unchanged {
+                path: last.to_string_lossy().into_owned(),
+            });
+        }
+    }
+
+    // 4. 容量の確認 (コピーは作業ファイルと同じサイズになる)
+    space::preflight(src, &target_dir, &target_dir, src_size, settings)?;
+
+    // 5. タイムスタンプ付きファイル名の生成 (例: filename_20260111_120000.ext)
+    let new_filename = utils::timestamped_name(src);
+
+    // 6. 保存先のフルパスを組み立て
+    let dest_path = target_dir.join(new_filename);
+    let dest_str = dest_path.to_string_lossy();
+
+    // 7. utils::copy_file (Sync処理付き) を実行
+    utils::copy_file(src, &dest_str, job)?;
+
+    // 8. 復元時に照合できるよう、コピーのハッシュを記録する (コピーの内容 = 復元結果)
+    let copied = checksum::hash_file(&dest_path)?;
+    checksum::record(
+        &target_dir,
+        &file_name_of(&dest_path),
+        checksum::ChecksumEntry {
+            file: copied.clone(),
+            source: Some(copied),
+            base: None,
+        },
+    )?;
+
+    // 9. 成功したら保存先のパスを返す (JS側での表示用)
+    Ok(BackupOutcome::Created {
+        path: dest_str.into_owned(),
+    })
+}
+
+/// フォルダ内で最も新しいコピー (timestamped_name の形式) を探す
+/// バックアップ ID は文字列のまま比べれば作成順に並ぶ (旧形式の秒単位 ID も含む)
+fn latest_copy(dir: &Path, src: &str) -> Result<Option<PathBuf>> {
+    let path = Path::new(src);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let ext = match path.extension() {
+        Some(e) => format!(r"\.{}", regex::escape(&e.to_string_lossy())),
+        None => String::new(),
+    };
+    let re = Regex::new(&format!(
+        r"^{}_(\d{{8}}_\d{{6}}(?:_\d{{6}})?){}$",
+        regex::escape(&stem),
+        ext
+    ))
+    .map_err(BackupError::internal)?;
+
+    let mut latest: Option<(String, PathBuf)> = None;
+    for entry in fs::read_dir(dir).at(dir)? {
+        let p = entry.at(dir)?.path();
+        let name = p
+            .file_name()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let id = match re.captures(&name) {
+            Some(caps) if p.is_file() => caps[1].to_string(),
+            _ => continue,
+        };
+        if latest
+            .as_ref()
+            .map_or(true, |(latest_id, _)| id > *latest_id)
+        {
+            latest = Some((id, p));
+        }
+    }
+    Ok(latest.map(|(_, p)| p))
+}
+
+/// zip / tar.gz でアーカイブしてバックアップする
+pub fn archive_backup_file(
+    src: &str,
+    backup_dir: &str,
+    format: &str,
+    password: &str,
+    settings: &BackupSettings,
+    job: &JobContext,
+) -> Result<String> {
+    // 1. バックアップ先の決定
+    let target_dir = if backup_dir.is_empty() {
+        utils::default_backup_dir(src)
+    } else {
+        std::path::PathBuf::from(backup_dir)
+    };
+
+    if !target_dir.exists() {
+        fs::create_dir_all(&target_dir).at(&target_dir)?;
+    }
+
+    // 2. 容量の確認 (圧縮後のサイズは分からないため、作業ファイルのサイズで見積もる)
+    let src_size = fs::metadata(src).at(src)?.len();
+    space::preflight(src, &target_dir, &target_dir, src_size, settings)?;
+
+    // 3. フォーマットによる分岐
+    let (archive_path, source) = if format == "zip" {
+        utils::zip_backup_file(src, &target_dir, password, job)?
+    } else {
+        utils::tar_backup_file(src, &target_dir, job)?
+    };
+
+    // 4. 復元時に照合できるよう、アーカイブに入れた作業ファイルのハッシュを記録する
+    checksum::record_file(&target_dir, &archive_path, Some(source))?;
+
+    // 5. 作成したアーカイブのパスを返す
+    Ok(archive_path.to_string_lossy().into_owned())
+}
+
+/// バックアップ先を使うテストで共通に使う補助
+#[cfg(test)]
+pub(crate) mod test_util {
+    use crate::progress::JobContext;
+    use crate::types::{BackupOutcome, BackupSettings};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tempfile::TempDir;
+
+    /// 一時フォルダ内の作業ファイル work.clip と、そのバックアップ先 cg_backup_work
+    pub struct Fixture {
+        _dir: TempDir,
+        pub work: PathBuf,
+        pub root: PathBuf,
+    }
+
+    impl Fixture {
+        pub fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            let work = dir.path().join("work.clip");
+            let root = dir.path().join("cg_backup_work");
+            fs::create_dir(&root).unwrap();
+            Self {
+                _dir: dir,
+                work,
+                root,
+            }
+        }
+
+        pub fn work_file(&self) -> String {
+            self.work.to_string_lossy().into_owned()
+        }
+
+        pub fn backup_dir(&self) -> String {
+            self.root.to_string_lossy().into_owned()
+        }
+
+        /// バックアップ ID が id のフルコピーを置く
+        pub fn copy(&self, id: &str) -> PathBuf {
+            let path = self.root.join(format!("work_{}.clip", id));
+            fs::write(&path, id).unwrap();
+            path
+        }
+
+        /// 作業ファイルを data に書き換えて差分バックアップする
+        /// (中身を丸ごと変えると差分が閾値を超え、新しい世代になる。100KB を超えるファイルのみ)
+        pub fn diff(&self, data: &[u8]) -> PathBuf {
+            self.diff_with(data, &BackupSettings::default())
+        }
+
+        pub fn diff_with(&self, data: &[u8], settings: &BackupSettings) -> PathBuf {
+            fs::write(&self.work, data).unwrap();
+            let outcome = super::backup_or_diff(
+                &self.work_file(),
+                &self.backup_dir(),
+                "hdiff",
+                "zstd",
+                settings,
+                &JobContext::default(),
+            )
+            .unwrap();
+            match outcome {
+                BackupOutcome::Created { path } => PathBuf::from(path),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    /// 差分と同じ世代フォルダにある .base
+    pub fn base_of(diff: &Path) -> PathBuf {
+        diff.with_file_name("work.clip.base")
+    }
+}
@@ -0,0 +1,174 @@
+use crate::checksum::{self, FileHash};
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::progress::{JobContext, Phase};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+
+// bsdiff 形式の差分ファイルを識別するためのヘッダ
+// 旧形式 (hdiffz 出力) との判別に使う
+const BSDIFF_MAGIC: &[u8; 8] = b"WBTBSD01";
+
+/// 差分ファイルが bsdiff 形式かどうかをヘッダで判定する
+pub fn is_bsdiff_file(diff_file: &str) -> bool {
+    let mut header = [0u8; 8];
+    match File::open(diff_file) {
+        Ok(mut f) => f.read_exact(&mut header).is_ok() && &header == BSDIFF_MAGIC,
+        Err(_) => false,
+    }
+}
+
+/// bsdiff で差分を作成する (外部プロセスを使わずプロセス内で完結)
+/// 新旧ファイルを丸ごとメモリに載せるため、呼び出し側でサイズ上限を確認すること
+/// bsdiff の計算自体は途中で止められないため、中断は計算の前後で確認する
+/// 差分の元にした作業ファイルの内容のハッシュを返す
+/// 失敗・中断した場合は書きかけの diff_file を残さない
+pub fn create_bsdiff(
+    old_file: &str,
+    new_file: &str,
+    diff_file: &str,
+    job: &JobContext,
+) -> Result<FileHash> {
+    let old = fs::read(old_file)
+        .at(old_file)
+        .context("Failed to read base file")?;
+    let new = fs::read(new_file)
+        .at(new_file)
+        .context("Failed to read work file")?;
+    let total = new.len() as u64;
+
+    // 1. 生の bsdiff パッチを作成
+    job.check()?;
+    job.report(Phase::Diffing, 0, total);
+    let mut patch = Vec::new();
+    bsdiff::diff(&old, &new, &mut patch).context("bsdiff error")?;
+    job.check()?;
+    job.report(Phase::Compressing, total, total);
+
+    // 2. ヘッダ + zlib 圧縮したパッチを書き出す
+    if let Err(e) = write_patch(diff_file, &patch) {
+        let _ = fs::remove_file(diff_file);
+        return Err(e);
+    }
+
+    Ok(checksum::hash_bytes(&new))
+}
+
+// 生パッチはゼロ埋めが多く、そのままだと作業ファイルと同程度のサイズになるため zlib で圧縮する
+fn write_patch(diff_file: &str, patch: &[u8]) -> Result<()> {
+    let mut out = File::create(diff_file)
+        .at(diff_file)
+        .context("Failed to create diff file")?;
+    out.write_all(BSDIFF_MAGIC).at(diff_file)?;
+
+    let mut enc = ZlibEncoder::new(out, Compression::default());
+    enc.write_all(patch).at(diff_file)?;
+    let out = enc.finish().at(diff_file)?;
+    out.sync_all().at(diff_file).context("Failed to sync file")
+}
+
+/// bsdiff 形式の差分を適用して復元する
+/// 復元結果もメモリ上で組み立てる (bsdiff の差分は bsdiff_max_file_size 以下のファイルからしか作らない)
+pub fn apply_bsdiff(
+    base_full: &str,
+    diff_file: &str,
+    out_path: &str,
+    job: &JobContext,
+) -> Result<()> {
+    let old = read_base(base_full)?;
+    job.check()?;
+    job.report(Phase::Restoring, 0, old.len() as u64);
+
+    let mut f = File::open(diff_file).at(diff_file)?;
+    let mut header = [0u8; 8];
+    f.read_exact(&mut header).at(diff_file)?;
+    if &header != BSDIFF_MAGIC {
+        return Err(BackupError::new(ErrorKind::Corrupt, "Not a bsdiff file").with_path(diff_file));
+    }
+
+    let mut dec = ZlibDecoder::new(f);
+    let mut new = Vec::new();
+    bsdiff::patch(&old, &mut dec, &mut new)
+        .map_err(|e| BackupError::new(ErrorKind::Corrupt, "bsdiff patch error").with_source(e))
+        .at(diff_file)?;
+
+    job.check()?;
+    job.report(Phase::Finalizing, new.len() as u64, new.len() as u64);
+
+    let mut out = File::create(out_path)
+        .at(out_path)
+        .context("Failed to create file")?;
+    out.write_all(&new).at(out_path)?;
+    out.sync_all().at(out_path).context("Failed to sync file")?;
+
+    Ok(())
+}
+
+/// 差分の元になる .base を読み込む (見つからない場合は BaseMissing)
+pub(crate) fn read_base(base_full: &str) -> Result<Vec<u8>> {
+    fs::read(base_full).map_err(|e| {
+        let kind = if e.kind() == std::io::ErrorKind::NotFound {
+            ErrorKind::BaseMissing
+        } else {
+            ErrorKind::Io
+        };
+        BackupError::new(kind, "Failed to read base file")
+            .with_path(base_full)
+            .with_source(e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hdiff_common::test_util::{edited, noise};
+    use tempfile::TempDir;
+
+    // old -> new の差分を作って復元する。差分のサイズを返す
+    fn round_trip(old: &[u8], new: &[u8]) -> u64 {
+        let job = JobContext::default();
+        crate::hdiff_common::test_util::round_trip(
+            old,
+            new,
+            |old, new, diff| create_bsdiff(old, new, diff, &job),
+            |old, diff, out| {
+                assert!(is_bsdiff_file(diff));
+                apply_bsdiff(old, diff, out, &job)
+            },
+        )
+    }
+
+    #[test]
+    fn round_trip_edited() {
+        let old = noise(100_000, 1);
+        let new = edited(&old);
+        let size = round_trip(&old, &new);
+        assert!(size < new.len() as u64 / 10, "{}", size);
+    }
+
+    #[test]
+    fn round_trip_empty_inputs() {
+        let data = noise(10_000, 3);
+        round_trip(&[], &[]);
+        round_trip(&[], &data);
+        round_trip(&data, &[]);
+    }
+
+    #[test]
+    fn round_trip_identical_inputs() {
+        let data = noise(100_000, 4);
+        let size = round_trip(&data, &data);
+        assert!(size < 1000, "{}", size);
+    }
+
+    #[test]
+    fn apply_reports_missing_base() {
+        let dir = TempDir::new().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        let job = JobContext::default();
+        let err = apply_bsdiff(&path("none"), &path("diff"), &path("out"), &job).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::BaseMissing);
+    }
+}
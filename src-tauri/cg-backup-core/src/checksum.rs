@@ -0,0 +1,275 @@
+use crate::atomic;
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// 世代フォルダ (baseN_...) ごとに置くハッシュ記録のファイル名
+pub const CHECKSUM_FILE: &str = "checksum.json";
+
+const HASH_ALGORITHM: &str = "blake3";
+
+/// ファイル 1 つ分のハッシュとサイズ
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FileHash {
+    pub hash: String,
+    pub size: u64,
+}
+
+/// 差分の元になった .base (同じフォルダ内のファイル名と、差分を作った時点のハッシュ)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BaseRef {
+    pub name: String,
+    #[serde(flatten)]
+    pub file: FileHash,
+}
+
+/// checksum.json の 1 エントリ
+/// .diff の場合は、バックアップ時点の作業ファイル (= 復元結果) のハッシュと、元にした .base も持つ
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChecksumEntry {
+    #[serde(flatten)]
+    pub file: FileHash,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<FileHash>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<BaseRef>,
+}
+
+/// checksum.json の中身 (キーはフォルダ内のファイル名)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChecksumFile {
+    pub algorithm: String,
+    pub files: BTreeMap<String, ChecksumEntry>,
+}
+
+impl Default for ChecksumFile {
+    fn default() -> Self {
+        Self {
+            algorithm: HASH_ALGORITHM.to_string(),
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+/// ファイルの BLAKE3 ハッシュ (16進) とサイズを計算する
+pub fn hash_file(path: impl AsRef<Path>) -> Result<FileHash> {
+    let path = path.as_ref();
+    let mut f = File::open(path).at(path)?;
+    let mut hasher = blake3::Hasher::new();
+    let size = io::copy(&mut f, &mut hasher).at(path)?;
+    Ok(FileHash {
+        hash: hasher.finalize().to_hex().to_string(),
+        size,
+    })
+}
+
+/// 読み込んだ内容のハッシュを計算しながら読む
+/// 差分エンジンが実際に読んだバイト列のハッシュを、別に読み直さずに得るために使う
+pub struct HashReader<R> {
+    inner: R,
+    hasher: blake3::Hasher,
+    size: u64,
+}
+
+impl<R: Read> HashReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: blake3::Hasher::new(),
+            size: 0,
+        }
+    }
+
+    /// ここまでに読んだ内容のハッシュとサイズ
+    pub fn finish(&self) -> FileHash {
+        FileHash {
+            hash: self.hasher.finalize().to_hex().to_string(),
+            size: self.size,
+        }
+    }
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+}
+
+/// メモリ上のデータの BLAKE3 ハッシュとサイズ
+pub fn hash_bytes(data: &[u8]) -> FileHash {
+    FileHash {
+        hash: blake3::hash(data).to_hex().to_string(),
+        size: data.len() as u64,
+    }
+}
+
+/// フォルダの checksum.json を読み込む (無ければ None)
+pub fn load(dir: &Path) -> Result<Option<ChecksumFile>> {
+    let path = dir.join(CHECKSUM_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read_to_string(&path).at(&path)?;
+    let file: ChecksumFile = serde_json::from_str(&data).map_err(|e| {
+        BackupError::new(ErrorKind::Corrupt, "Invalid checksum file")
+            .with_path(&path)
+            .with_source(e)
+    })?;
+    if file.algorithm != HASH_ALGORITHM {
+        return Err(BackupError::new(
+            ErrorKind::Unsupported,
+            format!("Unsupported checksum algorithm: {}", file.algorithm),
+        )
+        .with_path(&path));
+    }
+    Ok(Some(file))
+}
+
+/// フォルダの checksum.json に 1 件追記 (同名は上書き) する
+pub fn record(dir: &Path, file_name: &str, entry: ChecksumEntry) -> Result<()> {
+    let mut checksums = load(dir)?.unwrap_or_default();
+    checksums.files.insert(file_name.to_string(), entry);
+    save(dir, &checksums)
+}
+
+/// 削除したファイルを checksum.json から取り除く (空になったら checksum.json も消す)
+pub fn forget(dir: &Path, file_names: &[String]) -> Result<()> {
+    let mut checksums = match load(dir)? {
+        Some(c) => c,
+        None => return Ok(()),
+    };
+    for name in file_names {
+        checksums.files.remove(name);
+    }
+    if checksums.files.is_empty() {
+        let path = dir.join(CHECKSUM_FILE);
+        return fs::remove_file(&path).at(&path);
+    }
+    save(dir, &checksums)
+}
+
+/// ファイルのハッシュを計算して checksum.json に記録する
+/// source には、差分の場合にバックアップ時点の作業ファイルのハッシュを渡す
+pub fn record_file(dir: &Path, path: &Path, source: Option<FileHash>) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file = hash_file(path)?;
+    record(
+        dir,
+        &file_name,
+        ChecksumEntry {
+            file,
+            source,
+            base: None,
+        },
+    )
+}
+
+/// 差分のハッシュを、元にした .base (同じフォルダ) とともに checksum.json に記録する
+/// source にはバックアップ時点の作業ファイルのハッシュを渡す
+/// .base のハッシュは記録済みならそれを使い、無ければ計算する
+pub fn record_diff(dir: &Path, diff_path: &Path, source: FileHash, base_path: &Path) -> Result<()> {
+    let file_name = file_name_of(diff_path);
+    let base_name = file_name_of(base_path);
+    let mut checksums = load(dir)?.unwrap_or_default();
+    let base_hash = match checksums.files.get(&base_name) {
+        Some(entry) => entry.file.clone(),
+        None => hash_file(base_path)?,
+    };
+    let entry = ChecksumEntry {
+        file: hash_file(diff_path)?,
+        source: Some(source),
+        base: Some(BaseRef {
+            name: base_name,
+            file: base_hash,
+        }),
+    };
+    checksums.files.insert(file_name, entry);
+    save(dir, &checksums)
+}
+
+/// 差分を作ったときに記録した .base を返す (記録の無い古い差分は None)
+/// checksum.json が読めない場合も None にし、呼び出し側の名前による推定に任せる
+pub fn recorded_base(diff_path: &Path) -> Option<BaseRef> {
+    let dir = diff_path.parent().unwrap_or_else(|| Path::new("."));
+    match load(dir) {
+        Ok(checksums) => checksums?.files.get(&file_name_of(diff_path))?.base.clone(),
+        Err(e) => {
+            log::warn!("Failed to read base record for {:?}: {}", diff_path, e);
+            None
+        }
+    }
+}
+
+/// フォルダ内で最も新しい作業ファイルの差分と、そのバックアップ時点のハッシュを返す
+/// (差分名 "作業ファイル名.日時.algo.diff" の日時順。ファイルが消えている記録は無視する)
+pub fn latest_diff(dir: &Path, work_file_name: &str) -> Result<Option<(PathBuf, FileHash)>> {
+    let checksums = match load(dir)? {
+        Some(c) => c,
+        None => return Ok(None),
+    };
+    let latest = checksums
+        .files
+        .into_iter()
+        .rev()
+        .filter(|(name, _)| utils::diff_source_name(name).as_deref() == Some(work_file_name))
+        .filter_map(|(name, entry)| Some((dir.join(name), entry.source?)))
+        .find(|(path, _)| path.is_file());
+    Ok(latest)
+}
+
+/// バックアップ (差分・フルコピー・アーカイブ) から復元したファイルが、
+/// バックアップ時点の作業ファイルと一致するか確認する
+/// checksum.json に記録の無い古いバックアップは確認できないため、そのまま通す
+pub fn verify_restored(backup_file: &Path, restored: &Path) -> Result<()> {
+    let dir = backup_file.parent().unwrap_or_else(|| Path::new("."));
+    let backup_name = file_name_of(backup_file);
+
+    let expected = match load(dir)?.and_then(|c| c.files.get(&backup_name)?.source.clone()) {
+        Some(source) => source,
+        None => {
+            log::debug!("No source hash recorded for {}", backup_name);
+            return Ok(());
+        }
+    };
+
+    let actual = hash_file(restored)?;
+    if actual != expected {
+        return Err(BackupError::new(
+            ErrorKind::Corrupt,
+            "Restored file does not match the hash recorded at backup time",
+        )
+        .with_path(backup_file));
+    }
+    Ok(())
+}
+
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn save(dir: &Path, checksums: &ChecksumFile) -> Result<()> {
+    let path = dir.join(CHECKSUM_FILE);
+    let data = serde_json::to_string_pretty(checksums).map_err(BackupError::internal)?;
+
+    // 書き込み途中で落ちても既存の記録を壊さないよう、一時ファイル経由で置き換える
+    atomic::write_file(&path, |mut file| {
+        file.write_all(data.as_bytes()).at(&path)?;
+        Ok(file)
+    })
+}
@@ -0,0 +1,260 @@
+use crate::checksum;
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::index;
+use crate::manifest;
+use crate::pin;
+use crate::utils;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// バックアップ 1 件を削除する (メモ .note も一緒に消す)
+/// .base を指定した場合、それを使う差分が残っていれば cascade が無い限り削除しない
+/// ピン留めされたバックアップは削除しない
+/// 成功したら削除したファイルのパスを返す
+pub fn delete_backup(path: &str, cascade: bool) -> Result<Vec<String>> {
+    let target = Path::new(path);
+    fs::metadata(target).at(target)?;
+    let dir = target.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = target
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut targets = Vec::new();
+    if let Some(stem) = file_name.strip_suffix(".base") {
+        // .base に依存する差分 (同じフォルダの "作業ファイル名.*.diff")
+        let dependents = dependent_diffs(dir, stem)?;
+        if !dependents.is_empty() && !cascade {
+            return Err(BackupError::new(
+                ErrorKind::InUse,
+                format!("Base file is still used by {} diff(s)", dependents.len()),
+            )
+            .with_path(target));
+        }
+        targets.extend(dependents);
+    }
+    targets.push(target.to_path_buf());
+
+    // 1 件でもピン留めがあれば何も消さない
+    if let Some(pinned) = targets.iter().find(|p| pin::is_pinned(p)) {
+        return Err(pinned_error(pinned));
+    }
+
+    let mut deleted = Vec::new();
+    let mut names = Vec::new();
+    for t in &targets {
+        remove_with_sidecars(t, &mut deleted)?;
+        if let Some(name) = t.file_name() {
+            names.push(name.to_string_lossy().into_owned());
+        }
+    }
+    // フルコピー・アーカイブのハッシュはバックアップ先直下の checksum.json にある
+    checksum::forget(dir, &names)?;
+    if is_generation_dir(dir) {
+        manifest::forget(dir, &names)?;
+        // 空になった世代フォルダは片付ける
+        if fs::read_dir(dir).at(dir)?.next().is_none() {
+            fs::remove_dir(dir).at(dir)?;
+        }
+    }
+    // フォルダの更新日時だけでは削除を見逃すことがあるため、索引にも反映しておく
+    index::refresh(target);
+    Ok(deleted)
+}
+
+/// 世代フォルダ (baseN_...) から、作業ファイルの .base と差分をまとめて削除する
+/// 世代フォルダは同じバックアップ先を使う作業ファイルで共有されるため、他の作業ファイルのものは残し、
+/// フォルダは空になったときだけ消す
+/// ピン留めされたバックアップを含む場合は削除しない
+/// 成功したら削除したファイルのパスを返す
+pub fn delete_generation(work_file: &str, gen_dir: &str) -> Result<Vec<String>> {
+    let dir = Path::new(gen_dir.trim_end_matches(['/', '\\']));
+    if !is_generation_dir(dir) {
+        return Err(
+            BackupError::new(ErrorKind::InvalidInput, "Not a generation folder").with_path(dir),
+        );
+    }
+    if !dir.is_dir() {
+        return Err(
+            BackupError::new(ErrorKind::NotFound, "Generation folder not found").with_path(dir),
+        );
+    }
+    let work_name = Path::new(work_file)
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    // 作業ファイルの .base と差分だけを対象にする (.note は remove_with_sidecars が一緒に消す)
+    let manifest = manifest::load_or_warn(dir);
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).at(dir)? {
+        let path = entry.at(dir)?.path();
+        let name = path
+            .file_name()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !path.is_file() || !(name.ends_with(".base") || name.ends_with(".diff")) {
+            continue;
+        }
+        if manifest::source_name(manifest.as_ref(), &path)
+            .is_some_and(|owner| utils::same_file_name(&owner, &work_name))
+        {
+            files.push(path);
+        }
+    }
+    if files.is_empty() {
+        return Err(BackupError::new(
+            ErrorKind::NotFound,
+            format!("No backups of {} in this generation folder", work_name),
+        )
+        .with_path(dir));
+    }
+    if let Some(pinned) = files.iter().find(|p| pin::is_pinned(p)) {
+        return Err(pinned_error(pinned));
+    }
+
+    let mut deleted = Vec::new();
+    let mut names = Vec::new();
+    for f in &files {
+        remove_with_sidecars(f, &mut deleted)?;
+        if let Some(name) = f.file_name() {
+            names.push(name.to_string_lossy().into_owned());
+        }
+    }
+    checksum::forget(dir, &names)?;
+    manifest::forget(dir, &names)?;
+    // 他の作業ファイルのバックアップが無ければフォルダも片付ける
+    if fs::read_dir(dir).at(dir)?.next().is_none() {
+        fs::remove_dir(dir).at(dir)?;
+    }
+    index::refresh(&files[0]);
+    Ok(deleted)
+}
+
+/// フォルダ名が baseN_... か
+pub fn is_generation_dir(dir: &Path) -> bool {
+    let re = Regex::new(r"^base\d+_").unwrap();
+    dir.file_name()
+        .map(|n| re.is_match(&n.to_string_lossy()))
+        .unwrap_or(false)
+}
+
+/// "stem.base" を元に作られた差分を列挙する
+/// checksum.json に元の .base が記録されていればそれに従い、無ければ名前が "stem.ID.*.diff" のものとみなす
+fn dependent_diffs(dir: &Path, stem: &str) -> Result<Vec<PathBuf>> {
+    let base_name = format!("{}.base", stem);
+    let checksums = checksum::load(dir)?.unwrap_or_default();
+    let mut list = Vec::new();
+    for entry in fs::read_dir(dir).at(dir)? {
+        let path = entry.at(dir)?.path();
+        let name = path
+            .file_name()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !path.is_file() || !name.ends_with(".diff") {
+            continue;
+        }
+        let depends = match checksums.files.get(&name).and_then(|e| e.base.as_ref()) {
+            Some(base) => base.name == base_name,
+            None => utils::diff_source_name(&name).as_deref() == Some(stem),
+        };
+        if depends {
+            list.push(path);
+        }
+    }
+    list.sort();
+    Ok(list)
+}
+
+fn remove_with_sidecars(path: &Path, deleted: &mut Vec<String>) -> Result<()> {
+    fs::remove_file(path).at(path)?;
+    deleted.push(path.to_string_lossy().into_owned());
+
+    let note = PathBuf::from(format!("{}.note", path.to_string_lossy()));
+    if note.exists() {
+        fs::remove_file(&note).at(&note)?;
+        deleted.push(note.to_string_lossy().into_owned());
+    }
+    Ok(())
+}
+
+fn pinned_error(path: &Path) -> BackupError {
+    BackupError::new(ErrorKind::Pinned, "Pinned backups cannot be deleted").with_path(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::test_util::{base_of, Fixture};
+    use crate::hdiff_common::test_util::noise;
+    use crate::history;
+
+    fn file_name(path: &Path) -> String {
+        path.file_name().unwrap().to_string_lossy().into_owned()
+    }
+
+    fn listed(f: &Fixture) -> Vec<String> {
+        history::get_backup_list(&f.work_file(), &f.backup_dir())
+            .unwrap()
+            .into_iter()
+            .map(|item| item.file_name)
+            .collect()
+    }
+
+    #[test]
+    fn base_with_diffs_is_in_use() {
+        let f = Fixture::new();
+        let diff = f.diff(&noise(1000, 1));
+        let base = base_of(&diff);
+
+        let err = delete_backup(&base.to_string_lossy(), false).unwrap_err();
+
+        assert_eq!(err.kind, ErrorKind::InUse);
+        assert!(base.exists() && diff.exists());
+    }
+
+    #[test]
+    fn cascade_deletes_the_base_with_its_diffs() {
+        let f = Fixture::new();
+        let data = noise(1000, 1);
+        let first = f.diff(&data);
+        let second = f.diff(&data[..900]);
+        let base = base_of(&first);
+        fs::write(format!("{}.note", second.to_string_lossy()), "memo").unwrap();
+
+        let deleted = delete_backup(&base.to_string_lossy(), true).unwrap();
+
+        assert_eq!(deleted.len(), 4); // 差分 2 件、メモ、.base
+        assert!(!first.exists() && !second.exists() && !base.exists());
+        // 空になった世代フォルダも片付ける
+        assert!(!first.parent().unwrap().exists());
+        assert!(listed(&f).is_empty());
+    }
+
+    #[test]
+    fn records_are_removed_with_the_file() {
+        let f = Fixture::new();
+        let data = noise(1000, 1);
+        let kept = f.diff(&data);
+        let removed = f.diff(&data[..900]);
+        let copy = f.copy("20240101_120000");
+        checksum::record_file(&f.root, &copy, None).unwrap();
+        let gen_dir = removed.parent().unwrap();
+        let (kept_name, removed_name) = (file_name(&kept), file_name(&removed));
+        assert_eq!(listed(&f).len(), 3);
+
+        delete_backup(&removed.to_string_lossy(), false).unwrap();
+        delete_backup(&copy.to_string_lossy(), false).unwrap();
+
+        let checksums = checksum::load(gen_dir).unwrap().unwrap();
+        assert!(checksums.files.contains_key(&kept_name));
+        assert!(!checksums.files.contains_key(&removed_name));
+        let manifest = manifest::load(gen_dir).unwrap().unwrap();
+        assert!(manifest.diff(&kept_name).is_some());
+        assert!(manifest.diff(&removed_name).is_none());
+        // バックアップ先直下の checksum.json は、最後の記録と一緒に消える
+        assert!(!f.root.join(checksum::CHECKSUM_FILE).exists());
+        assert_eq!(listed(&f), [kept_name]);
+    }
+}
@@ -0,0 +1,248 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// エラーの種類 (GUI / CLI はこれを見て処理や表示を切り替える)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    /// ファイルやフォルダが見つからない
+    NotFound,
+    /// アクセス権限がない
+    PermissionDenied,
+    /// ディスクの空き容量不足
+    DiskFull,
+    /// 作業ファイルごとの保存容量の上限 (quota) を超える
+    QuotaExceeded,
+    /// 引数やパスが不正
+    InvalidInput,
+    /// 差分に対応する .base が見つからない
+    BaseMissing,
+    /// バックアップが壊れている (ヘッダ不正 / 展開失敗など)
+    Corrupt,
+    /// 対応していない形式
+    Unsupported,
+    /// ファイルが大きすぎる (bsdiff の上限など)
+    TooLarge,
+    /// ピン留めされたバックアップは削除できない
+    Pinned,
+    /// 差分が使っている .base など、まだ必要なファイル
+    InUse,
+    /// ユーザーが処理を中断した
+    Cancelled,
+    /// 外部バイナリ (hpatchz) が無い、または異常終了した
+    Sidecar,
+    /// 設定ファイルの読み書きに失敗した
+    Config,
+    /// 上記以外の I/O エラー
+    Io,
+    /// 内部エラー (スレッドやウィンドウ操作の失敗など)
+    Internal,
+}
+
+impl ErrorKind {
+    /// フロントエンドの i18n 辞書 (AppConfig.json) のキー
+    pub fn i18n_key(self) -> &'static str {
+        match self {
+            ErrorKind::NotFound => "errorNotFound",
+            ErrorKind::PermissionDenied => "errorPermissionDenied",
+            ErrorKind::DiskFull => "errorDiskFull",
+            ErrorKind::QuotaExceeded => "errorQuotaExceeded",
+            ErrorKind::InvalidInput => "errorInvalidInput",
+            ErrorKind::BaseMissing => "errorBaseMissing",
+            ErrorKind::Corrupt => "errorCorrupt",
+            ErrorKind::Unsupported => "errorUnsupported",
+            ErrorKind::TooLarge => "errorTooLarge",
+            ErrorKind::Pinned => "errorPinned",
+            ErrorKind::InUse => "errorInUse",
+            ErrorKind::Cancelled => "errorCancelled",
+            ErrorKind::Sidecar => "errorSidecar",
+            ErrorKind::Config => "errorConfig",
+            ErrorKind::Io => "errorIo",
+            ErrorKind::Internal => "errorInternal",
+        }
+    }
+
+    fn from_io(e: &io::Error) -> Self {
+        // io::ErrorKind::StorageFull / QuotaExceeded / InvalidFilename は Rust 1.83 からのため、
+        // MSRV (1.77) でも判定できるよう OS のエラーコードを見る
+        if let Some(code) = e.raw_os_error() {
+            if DISK_FULL_CODES.contains(&code) {
+                return ErrorKind::DiskFull;
+            }
+            if INVALID_NAME_CODES.contains(&code) {
+                return ErrorKind::InvalidInput;
+            }
+        }
+        match e.kind() {
+            io::ErrorKind::NotFound => ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+            io::ErrorKind::InvalidInput => ErrorKind::InvalidInput,
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => ErrorKind::Corrupt,
+            _ => ErrorKind::Io,
+        }
+    }
+}
+
+// 容量不足 (ENOSPC / EDQUOT、Windows は ERROR_HANDLE_DISK_FULL / ERROR_DISK_FULL / ERROR_DISK_QUOTA_EXCEEDED)
+// と、ファイル名が不正・長すぎる (ENAMETOOLONG、Windows は ERROR_INVALID_NAME / ERROR_FILENAME_EXCED_RANGE) の OS のエラーコード
+#[cfg(windows)]
+const DISK_FULL_CODES: &[i32] = &[39, 112, 1295];
+#[cfg(windows)]
+const INVALID_NAME_CODES: &[i32] = &[123, 206];
+#[cfg(target_os = "macos")]
+const DISK_FULL_CODES: &[i32] = &[28, 69];
+#[cfg(target_os = "macos")]
+const INVALID_NAME_CODES: &[i32] = &[63];
+#[cfg(not(any(windows, target_os = "macos")))]
+const DISK_FULL_CODES: &[i32] = &[28, 122];
+#[cfg(not(any(windows, target_os = "macos")))]
+const INVALID_NAME_CODES: &[i32] = &[36];
+
+/// バックアップエンジンのエラー
+/// 種類・対象パス・説明・原因を持ち、JS には {kind, path, message, source, i18nKey} として渡る
+#[derive(Debug)]
+pub struct BackupError {
+    pub kind: ErrorKind,
+    pub path: Option<PathBuf>,
+    pub message: String,
+    pub source: Option<Box<dyn StdError + Send + Sync>>,
+}
+
+pub type Result<T> = std::result::Result<T, BackupError>;
+
+impl BackupError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            path: None,
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// スレッドやウィンドウ操作など、バックアップ以外の失敗を包む
+    pub fn internal(e: impl fmt::Display) -> Self {
+        Self::new(ErrorKind::Internal, e.to_string())
+    }
+
+    /// 対象パスを付ける (既に付いている場合は上書きしない)
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        if self.path.is_none() {
+            self.path = Some(path.as_ref().to_path_buf());
+        }
+        self
+    }
+
+    pub fn with_source(mut self, source: impl StdError + Send + Sync + 'static) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    pub fn i18n_key(&self) -> &'static str {
+        self.kind.i18n_key()
+    }
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(path) = &self.path {
+            write!(f, " ({})", path.display())?;
+        }
+        if let Some(source) = &self.source {
+            write!(f, ": {}", source)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for BackupError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source
+            .as_deref()
+            .map(|e| e as &(dyn StdError + 'static))
+    }
+}
+
+impl Serialize for BackupError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("BackupError", 5)?;
+        s.serialize_field("kind", &self.kind)?;
+        s.serialize_field("path", &self.path)?;
+        s.serialize_field("message", &self.message)?;
+        s.serialize_field("source", &self.source.as_ref().map(|e| e.to_string()))?;
+        s.serialize_field("i18nKey", self.i18n_key())?;
+        s.end()
+    }
+}
+
+impl From<io::Error> for BackupError {
+    fn from(e: io::Error) -> Self {
+        // Read / Write の中で起きた BackupError (中断など) は種類を引き継ぐ
+        if let Some(inner) = e.get_ref().and_then(|i| i.downcast_ref::<BackupError>()) {
+            return Self::new(inner.kind, inner.message.clone());
+        }
+        Self::new(ErrorKind::from_io(&e), e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for BackupError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(io_err) => io_err.into(),
+            zip::result::ZipError::UnsupportedArchive(_) => {
+                Self::new(ErrorKind::Unsupported, e.to_string())
+            }
+            zip::result::ZipError::InvalidPassword => {
+                Self::new(ErrorKind::InvalidInput, e.to_string())
+            }
+            _ => Self::new(ErrorKind::Corrupt, e.to_string()),
+        }
+    }
+}
+
+/// Result にパスや説明を付けるための拡張
+pub trait ResultExt<T> {
+    /// エラーに対象パスを付ける
+    fn at(self, path: impl AsRef<Path>) -> Result<T>;
+    /// エラーに説明を付ける (元のエラーは source に入り、種類は引き継ぐ)
+    fn context(self, message: impl Into<String>) -> Result<T>;
+}
+
+impl<T, E: Into<BackupError>> ResultExt<T> for std::result::Result<T, E> {
+    fn at(self, path: impl AsRef<Path>) -> Result<T> {
+        self.map_err(|e| e.into().with_path(path))
+    }
+
+    fn context(self, message: impl Into<String>) -> Result<T> {
+        self.map_err(|e| {
+            // パスは外側に移して、表示が重複しないようにする
+            let mut e: BackupError = e.into();
+            BackupError {
+                kind: e.kind,
+                path: e.path.take(),
+                message: message.into(),
+                source: Some(Box::new(e)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_full_is_detected_from_os_code() {
+        let err = BackupError::from(io::Error::from_raw_os_error(DISK_FULL_CODES[0]));
+        assert_eq!(err.kind, ErrorKind::DiskFull);
+        let err = BackupError::from(io::Error::from_raw_os_error(INVALID_NAME_CODES[0]));
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+        let err = BackupError::from(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(err.kind, ErrorKind::NotFound);
+    }
+}
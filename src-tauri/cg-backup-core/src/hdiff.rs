@@ -0,0 +1,725 @@
+use crate::bsdiff::read_base;
+use crate::checksum::{FileHash, HashReader};
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::progress::{JobContext, Phase};
+use bzip2::read::BzDecoder;
+use bzip2::write::BzEncoder;
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+use flate2::write::{DeflateEncoder, ZlibEncoder};
+use liblzma::read::XzDecoder;
+use liblzma::stream::{LzmaOptions, Stream};
+use liblzma::write::XzEncoder;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{BuildHasherDefault, Hasher};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::Command;
+
+// プロセス内エンジンで作成した差分ファイルのヘッダ
+// hdiffz (Sidecar) が作成した旧形式との判別に使う
+const HDIFF_MAGIC: &[u8; 8] = b"WBTHDF01";
+
+// 差分ストリーム内の命令
+const OP_COPY: u8 = 0; // .base の範囲をコピー
+const OP_ADD: u8 = 1; // 新規データをそのまま書き込む
+
+// ブロック索引の件数上限 (巨大ファイルでもメモリを食い過ぎないように)
+const MAX_INDEX_BLOCKS: usize = 1 << 22;
+const MIN_BLOCK_SIZE: usize = 64;
+const HASH_PRIME: u64 = 0x100000001b3;
+// 進捗の通知と中断の確認を行う間隔 (バイト)
+const PROGRESS_STEP: usize = 1 << 20;
+// 作業ファイルはこの単位で読み進め、丸ごとはメモリに載せない
+const READ_CHUNK: usize = 4 << 20;
+// 新しいファイルのサイズを書き込むヘッダ内の位置 (マジック / 圧縮方式 / 旧サイズの後)
+const NEW_SIZE_OFFSET: u64 = 17;
+
+/// 差分本体の圧縮方式 (hdiffz の -c-xxx に対応)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compressor {
+    None,
+    Zstd,
+    Lzma2,
+    Lzma,
+    Zlib,
+    Ldef,
+    Bzip2,
+}
+
+impl Compressor {
+    /// UI から渡される名前を変換する (未知の指定は従来通り zstd)
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "none" => Compressor::None,
+            "zstd" => Compressor::Zstd,
+            "lzma2" => Compressor::Lzma2,
+            "lzma" => Compressor::Lzma,
+            "zlib" => Compressor::Zlib,
+            "ldef" => Compressor::Ldef,
+            // pbzip2 は出力形式が bzip2 と互換
+            "pbzip2" | "bzip2" => Compressor::Bzip2,
+            _ => Compressor::Zstd,
+        }
+    }
+
+    /// manifest に記録する名前
+    pub fn name(self) -> &'static str {
+        match self {
+            Compressor::None => "none",
+            Compressor::Zstd => "zstd",
+            Compressor::Lzma2 => "lzma2",
+            Compressor::Lzma => "lzma",
+            Compressor::Zlib => "zlib",
+            Compressor::Ldef => "ldef",
+            Compressor::Bzip2 => "bzip2",
+        }
+    }
+
+    fn id(self) -> u8 {
+        match self {
+            Compressor::None => 0,
+            Compressor::Zstd => 1,
+            Compressor::Lzma2 => 2,
+            Compressor::Lzma => 3,
+            Compressor::Zlib => 4,
+            Compressor::Ldef => 5,
+            Compressor::Bzip2 => 6,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self> {
+        Ok(match id {
+            0 => Compressor::None,
+            1 => Compressor::Zstd,
+            2 => Compressor::Lzma2,
+            3 => Compressor::Lzma,
+            4 => Compressor::Zlib,
+            5 => Compressor::Ldef,
+            6 => Compressor::Bzip2,
+            _ => {
+                return Err(BackupError::new(
+                    ErrorKind::Unsupported,
+                    format!("Unknown compressor id: {}", id),
+                ))
+            }
+        })
+    }
+
+    fn encoder<W: Write>(self, w: W) -> io::Result<Encoder<W>> {
+        Ok(match self {
+            Compressor::None => Encoder::None(w),
+            Compressor::Zstd => Encoder::Zstd(zstd::Encoder::new(w, 0)?),
+            Compressor::Lzma2 => Encoder::Xz(XzEncoder::new(w, 6)),
+            Compressor::Lzma => {
+                let stream = Stream::new_lzma_encoder(&LzmaOptions::new_preset(6)?)?;
+                Encoder::Xz(XzEncoder::new_stream(w, stream))
+            }
+            Compressor::Zlib => Encoder::Zlib(ZlibEncoder::new(w, flate2::Compression::default())),
+            Compressor::Ldef => {
+                Encoder::Ldef(DeflateEncoder::new(w, flate2::Compression::default()))
+            }
+            Compressor::Bzip2 => Encoder::Bzip2(BzEncoder::new(w, bzip2::Compression::default())),
+        })
+    }
+
+    fn decoder<'a, R: Read + 'a>(self, r: R) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compressor::None => Box::new(r),
+            Compressor::Zstd => Box::new(zstd::Decoder::new(r)?),
+            Compressor::Lzma2 => Box::new(XzDecoder::new(r)),
+            Compressor::Lzma => Box::new(XzDecoder::new_stream(
+                r,
+                Stream::new_lzma_decoder(u64::MAX)?,
+            )),
+            Compressor::Zlib => Box::new(ZlibDecoder::new(r)),
+            Compressor::Ldef => Box::new(DeflateDecoder::new(r)),
+            Compressor::Bzip2 => Box::new(BzDecoder::new(r)),
+        })
+    }
+}
+
+// 圧縮方式ごとのエンコーダ (finish で末尾まで確実に書き切るため enum で保持する)
+enum Encoder<W: Write> {
+    None(W),
+    Zstd(zstd::Encoder<'static, W>),
+    Xz(XzEncoder<W>),
+    Zlib(ZlibEncoder<W>),
+    Ldef(DeflateEncoder<W>),
+    Bzip2(BzEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    fn inner(&mut self) -> &mut dyn Write {
+        match self {
+            Encoder::None(w) => w,
+            Encoder::Zstd(e) => e,
+            Encoder::Xz(e) => e,
+            Encoder::Zlib(e) => e,
+            Encoder::Ldef(e) => e,
+            Encoder::Bzip2(e) => e,
+        }
+    }
+
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::None(w) => Ok(w),
+            Encoder::Zstd(e) => e.finish(),
+            Encoder::Xz(e) => e.finish(),
+            Encoder::Zlib(e) => e.finish(),
+            Encoder::Ldef(e) => e.finish(),
+            Encoder::Bzip2(e) => e.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner().flush()
+    }
+}
+
+// ハッシュ値そのものをキーにするため、HashMap 側での再ハッシュを省く
+#[derive(Default)]
+struct IdentityHasher(u64);
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, _: &[u8]) {
+        unreachable!("IdentityHasher only supports u64 keys")
+    }
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n;
+    }
+}
+
+type BlockIndex = HashMap<u64, usize, BuildHasherDefault<IdentityHasher>>;
+
+/// 差分ファイルがプロセス内エンジンの形式かどうかをヘッダで判定する
+pub fn is_native_hdiff_file(diff_file: &str) -> bool {
+    let mut header = [0u8; 8];
+    match File::open(diff_file) {
+        Ok(mut f) => f.read_exact(&mut header).is_ok() && &header == HDIFF_MAGIC,
+        Err(_) => false,
+    }
+}
+
+/// 差分を作成する (hdiffz -s 相当のブロック一致方式、プロセス内で完結)
+/// .base は一致箇所を探すためメモリに載せるが、作業ファイルは READ_CHUNK 単位で読み進める
+/// (必要なメモリは .base のサイズ + ブロック索引 + 数 MB)
+/// 差分の元にした (= 復元で得られる) 作業ファイルの内容のハッシュを返す
+/// 失敗・中断した場合は書きかけの diff_file を残さない
+pub fn create_hdiff(
+    old_file: &str,
+    new_file: &str,
+    diff_file: &str,
+    compress_algo: &str, // "zstd", "lzma2", "none" 等
+    job: &JobContext,
+) -> Result<FileHash> {
+    let result = write_hdiff(old_file, new_file, diff_file, compress_algo, job);
+    if result.is_err() {
+        let _ = fs::remove_file(diff_file);
+    }
+    result
+}
+
+fn write_hdiff(
+    old_file: &str,
+    new_file: &str,
+    diff_file: &str,
+    compress_algo: &str,
+    job: &JobContext,
+) -> Result<FileHash> {
+    let old = fs::read(old_file)
+        .at(old_file)
+        .context("Failed to read base file")?;
+    let new = File::open(new_file)
+        .at(new_file)
+        .context("Failed to read work file")?;
+    let total = new.metadata().at(new_file)?.len();
+    // 読みながらハッシュを取り、差分に入った内容そのものを記録できるようにする
+    let mut new = HashReader::new(new);
+    let compressor = Compressor::from_name(compress_algo);
+
+    let file = File::create(diff_file)
+        .at(diff_file)
+        .context("Failed to create diff file")?;
+    let mut out = BufWriter::new(file);
+
+    // 1. ヘッダ (マジック / 圧縮方式 / 新旧サイズ)
+    // 新しいサイズは読み終えてから実際に読んだバイト数で書き直す
+    let mut header = Vec::with_capacity(25);
+    header.extend_from_slice(HDIFF_MAGIC);
+    header.push(compressor.id());
+    header.extend_from_slice(&(old.len() as u64).to_le_bytes());
+    header.extend_from_slice(&total.to_le_bytes());
+    out.write_all(&header).at(diff_file)?;
+
+    // 2. 命令列を圧縮しながら書き出す
+    let mut enc = compressor
+        .encoder(out)
+        .at(diff_file)
+        .context("hdiff error")?;
+    let read = encode_ops(&old, &mut new, total, &mut enc, job)
+        .at(diff_file)
+        .context("hdiff error")?;
+    job.report(Phase::Compressing, read, read);
+    let out = enc.finish().at(diff_file).context("hdiff error")?;
+
+    // 3. 書き込み確定
+    let mut file = out.into_inner().map_err(|e| e.into_error()).at(diff_file)?;
+    if read != total {
+        file.seek(SeekFrom::Start(NEW_SIZE_OFFSET))
+            .and_then(|_| file.write_all(&read.to_le_bytes()))
+            .at(diff_file)?;
+    }
+    file.sync_all()
+        .at(diff_file)
+        .context("Failed to sync file")?;
+    Ok(new.finish())
+}
+
+/// プロセス内エンジンの差分を適用して復元する
+pub fn apply_hdiff(
+    base_full: &str,
+    diff_file: &str,
+    out_path: &str,
+    job: &JobContext,
+) -> Result<()> {
+    let old = read_base(base_full)?;
+
+    let mut reader = BufReader::new(File::open(diff_file).at(diff_file)?);
+    let mut header = [0u8; 25];
+    reader
+        .read_exact(&mut header)
+        .at(diff_file)
+        .context("Invalid diff file")?;
+    if &header[..8] != HDIFF_MAGIC {
+        return Err(
+            BackupError::new(ErrorKind::Corrupt, "Not a native hdiff file").with_path(diff_file),
+        );
+    }
+    let compressor = Compressor::from_id(header[8]).at(diff_file)?;
+    let old_size = u64::from_le_bytes(header[9..17].try_into().unwrap());
+    let new_size = u64::from_le_bytes(header[17..25].try_into().unwrap());
+
+    if old_size != old.len() as u64 {
+        return Err(BackupError::new(
+            ErrorKind::BaseMissing,
+            format!(
+                "Base file mismatch (expected {} bytes, found {} bytes)",
+                old_size,
+                old.len()
+            ),
+        )
+        .with_path(base_full));
+    }
+
+    let mut dec = compressor
+        .decoder(reader)
+        .at(diff_file)
+        .context("hpatch error")?;
+    let file = File::create(out_path)
+        .at(out_path)
+        .context("Failed to create file")?;
+    let mut out = BufWriter::new(file);
+
+    let written = decode_ops(&old, &mut dec, &mut out, new_size, job)
+        .at(diff_file)
+        .context("hpatch error")?;
+    if written != new_size {
+        return Err(BackupError::new(
+            ErrorKind::Corrupt,
+            format!(
+                "hpatch error: output size mismatch ({} / {} bytes)",
+                written, new_size
+            ),
+        )
+        .with_path(diff_file));
+    }
+
+    let file = out.into_inner().map_err(|e| e.into_error()).at(out_path)?;
+    file.sync_all()
+        .at(out_path)
+        .context("Failed to sync file")?;
+    Ok(())
+}
+
+/// hpatchz (旧 Sidecar) を呼び出してパッチを適用する
+/// プロセス内エンジン導入前に作成された旧形式の差分を復元するために残している
+pub fn apply_hdiff_legacy(
+    hpatchz: &Path,
+    base_full: &str,
+    diff_file: &str,
+    out_path: &str,
+) -> Result<()> {
+    let mut cmd = Command::new(hpatchz);
+    cmd.args(["-f", "-s", base_full, diff_file, out_path]);
+
+    // Windows ではコンソールウィンドウを出さない (CREATE_NO_WINDOW)
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x0800_0000);
+    }
+
+    let output = cmd.output().map_err(|e| {
+        BackupError::new(ErrorKind::Sidecar, "Failed to run hpatchz")
+            .with_path(hpatchz)
+            .with_source(e)
+    })?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let err_msg = String::from_utf8_lossy(&output.stderr);
+        Err(
+            BackupError::new(ErrorKind::Sidecar, format!("hpatchz error: {}", err_msg))
+                .with_path(diff_file),
+        )
+    }
+}
+
+// --- 差分エンジン本体 ---
+
+fn block_size_for(len: usize) -> usize {
+    // 索引の件数が上限を超えないようにブロックを大きくする
+    let size = len.div_ceil(MAX_INDEX_BLOCKS);
+    size.max(MIN_BLOCK_SIZE)
+}
+
+fn hash_block(data: &[u8]) -> u64 {
+    data.iter().fold(0u64, |h, &b| {
+        h.wrapping_mul(HASH_PRIME).wrapping_add(b as u64 + 1)
+    })
+}
+
+fn build_index(old: &[u8], block: usize) -> BlockIndex {
+    let mut index = BlockIndex::default();
+    index.reserve(old.len() / block);
+    for (i, chunk) in old.chunks_exact(block).enumerate() {
+        // 同じ内容のブロックは最初の位置を使う
+        index.entry(hash_block(chunk)).or_insert(i * block);
+    }
+    index
+}
+
+/// 作業ファイルを先頭から読み進めるための窓 (読み終えた部分は consume で捨てる)
+struct Window<R> {
+    reader: R,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Window<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::with_capacity(READ_CHUNK),
+            eof: false,
+        }
+    }
+
+    /// buf が need バイト以上になるまで読み足す (ファイルの終わりで足りなければ false)
+    fn fill(&mut self, need: usize) -> io::Result<bool> {
+        while self.buf.len() < need && !self.eof {
+            let read = self
+                .reader
+                .by_ref()
+                .take(READ_CHUNK as u64)
+                .read_to_end(&mut self.buf)?;
+            self.eof = read == 0;
+        }
+        Ok(self.buf.len() >= need)
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.buf.drain(..len);
+    }
+}
+
+/// new を読み進めながら命令列を書き出し、読んだバイト数を返す
+/// 新しいファイル側は窓の中だけで一致を伸ばすため、窓の境目では命令が分かれることがある
+fn encode_ops<R: Read, W: Write>(
+    old: &[u8],
+    new: R,
+    total: u64,
+    w: &mut W,
+    job: &JobContext,
+) -> io::Result<u64> {
+    job.report(Phase::Diffing, 0, total);
+    let block = block_size_for(old.len());
+    let index = if old.len() < block {
+        BlockIndex::default()
+    } else {
+        build_index(old, block)
+    };
+    // ローリングハッシュで先頭バイトを取り除くための係数 (HASH_PRIME^(block-1))
+    let top = (1..block).fold(1u64, |p, _| p.wrapping_mul(HASH_PRIME));
+
+    let mut win = Window::new(new);
+    // done は win.buf[0] のファイル内の位置。pos / lit_start は win.buf 内の位置
+    let mut done = 0u64;
+    let mut pos = 0;
+    let mut lit_start = 0;
+    let mut hash = None;
+    let mut next_report = PROGRESS_STEP as u64;
+
+    loop {
+        // 一致しないまま溜まった部分を書き出し、読み終えた部分を捨てる
+        if pos - lit_start >= READ_CHUNK {
+            write_add(w, &win.buf[lit_start..pos])?;
+            lit_start = pos;
+        }
+        if lit_start >= READ_CHUNK {
+            win.consume(lit_start);
+            done += lit_start as u64;
+            pos -= lit_start;
+            lit_start = 0;
+        }
+        if !win.fill(pos + block)? {
+            break;
+        }
+        if done + pos as u64 >= next_report {
+            job.check_io()?;
+            job.report(Phase::Diffing, done + pos as u64, total);
+            next_report = done + (pos + PROGRESS_STEP) as u64;
+        }
+
+        let h = hash.unwrap_or_else(|| hash_block(&win.buf[pos..pos + block]));
+        if let Some(&off) = index.get(&h) {
+            if old[off..off + block] == win.buf[pos..pos + block] {
+                // 一致範囲を前に伸ばす
+                let (mut s_new, mut s_old) = (pos, off);
+                while s_new > lit_start && s_old > 0 && win.buf[s_new - 1] == old[s_old - 1] {
+                    s_new -= 1;
+                    s_old -= 1;
+                }
+                write_add(w, &win.buf[lit_start..s_new])?;
+
+                // 後ろに伸ばす (窓の終わりに達したら、そこまでを書き出して読み足す)
+                let (mut e_new, mut e_old) = (pos + block, off + block);
+                loop {
+                    while e_new < win.buf.len() && e_old < old.len() && win.buf[e_new] == old[e_old]
+                    {
+                        e_new += 1;
+                        e_old += 1;
+                    }
+                    if e_new < win.buf.len() || e_old >= old.len() {
+                        break;
+                    }
+                    write_copy(w, s_old as u64, (e_new - s_new) as u64)?;
+                    win.consume(e_new);
+                    done += e_new as u64;
+                    (s_new, s_old, e_new) = (0, e_old, 0);
+                    job.check_io()?;
+                    job.report(Phase::Diffing, done, total);
+                    if !win.fill(1)? {
+                        break;
+                    }
+                }
+                if e_new > s_new {
+                    write_copy(w, s_old as u64, (e_new - s_new) as u64)?;
+                }
+
+                pos = e_new;
+                lit_start = pos;
+                hash = None;
+                continue;
+            }
+        }
+
+        hash = if win.fill(pos + block + 1)? {
+            let out_b = win.buf[pos] as u64 + 1;
+            let in_b = win.buf[pos + block] as u64 + 1;
+            Some(
+                h.wrapping_sub(out_b.wrapping_mul(top))
+                    .wrapping_mul(HASH_PRIME)
+                    .wrapping_add(in_b),
+            )
+        } else {
+            None
+        };
+        pos += 1;
+    }
+
+    write_add(w, &win.buf[lit_start..])?;
+    Ok(done + win.buf.len() as u64)
+}
+
+fn decode_ops<R: Read + ?Sized, W: Write>(
+    old: &[u8],
+    r: &mut R,
+    w: &mut W,
+    new_size: u64,
+    job: &JobContext,
+) -> io::Result<u64> {
+    let mut written = 0u64;
+    while written < new_size {
+        job.check_io()?;
+        job.report(Phase::Restoring, written, new_size);
+        let mut tag = [0u8; 1];
+        r.read_exact(&mut tag)?;
+        match tag[0] {
+            OP_COPY => {
+                let off = read_varint(r)? as usize;
+                let len = read_varint(r)? as usize;
+                let end = off
+                    .checked_add(len)
+                    .filter(|&end| end <= old.len())
+                    .ok_or_else(|| invalid_data("copy range is out of base file"))?;
+                w.write_all(&old[off..end])?;
+                written += len as u64;
+            }
+            OP_ADD => {
+                let len = read_varint(r)?;
+                let copied = io::copy(&mut r.take(len), w)?;
+                if copied != len {
+                    return Err(invalid_data("unexpected end of diff data"));
+                }
+                written += len;
+            }
+            _ => return Err(invalid_data("unknown diff operation")),
+        }
+    }
+    w.flush()?;
+    Ok(written)
+}
+
+fn write_add<W: Write + ?Sized>(w: &mut W, data: &[u8]) -> io::Result<()> {
+    if data.is_empty() {
+        return Ok(());
+    }
+    w.write_all(&[OP_ADD])?;
+    write_varint(w, data.len() as u64)?;
+    w.write_all(data)
+}
+
+fn write_copy<W: Write + ?Sized>(w: &mut W, off: u64, len: u64) -> io::Result<()> {
+    w.write_all(&[OP_COPY])?;
+    write_varint(w, off)?;
+    write_varint(w, len)
+}
+
+fn write_varint<W: Write + ?Sized>(w: &mut W, mut v: u64) -> io::Result<()> {
+    let mut buf = [0u8; 10];
+    let mut i = 0;
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            buf[i] = byte;
+            i += 1;
+            break;
+        }
+        buf[i] = byte | 0x80;
+        i += 1;
+    }
+    w.write_all(&buf[..i])
+}
+
+fn read_varint<R: Read + ?Sized>(r: &mut R) -> io::Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut b = [0u8; 1];
+        r.read_exact(&mut b)?;
+        v |= ((b[0] & 0x7f) as u64) << shift;
+        if b[0] & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(invalid_data("varint is too long"))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hdiff_common::test_util::{edited, noise};
+    use tempfile::TempDir;
+
+    const ALL: [Compressor; 7] = [
+        Compressor::None,
+        Compressor::Zstd,
+        Compressor::Lzma2,
+        Compressor::Lzma,
+        Compressor::Zlib,
+        Compressor::Ldef,
+        Compressor::Bzip2,
+    ];
+
+    // compressor で old -> new の差分を作って復元する。差分のサイズを返す
+    fn round_trip(old: &[u8], new: &[u8], compressor: Compressor) -> u64 {
+        let job = JobContext::default();
+        crate::hdiff_common::test_util::round_trip(
+            old,
+            new,
+            |old, new, diff| create_hdiff(old, new, diff, compressor.name(), &job),
+            |old, diff, out| {
+                assert!(is_native_hdiff_file(diff));
+                apply_hdiff(old, diff, out, &job)
+            },
+        )
+    }
+
+    #[test]
+    fn round_trip_each_compressor() {
+        let old = noise(300_000, 1);
+        let new = edited(&old);
+        for compressor in ALL {
+            let size = round_trip(&old, &new, compressor);
+            assert!(size < new.len() as u64 / 10, "{:?}: {}", compressor, size);
+        }
+    }
+
+    #[test]
+    fn round_trip_empty_inputs() {
+        let data = noise(10_000, 2);
+        for compressor in ALL {
+            round_trip(&[], &[], compressor);
+            round_trip(&[], &data, compressor);
+            round_trip(&data, &[], compressor);
+        }
+    }
+
+    #[test]
+    fn round_trip_identical_inputs() {
+        let data = noise(200_000, 3);
+        for compressor in ALL {
+            let size = round_trip(&data, &data, compressor);
+            assert!(size < 100, "{:?}: {}", compressor, size);
+        }
+    }
+
+    #[test]
+    fn round_trip_across_read_chunks() {
+        // 一致範囲とリテラルが READ_CHUNK の境目をまたいでも復元できる
+        let old = noise(READ_CHUNK * 2 + 12_345, 4);
+        let mut new = edited(&old);
+        new.extend(noise(READ_CHUNK + 777, 5));
+        let size = round_trip(&old, &new, Compressor::None);
+        assert!(size < (READ_CHUNK * 2) as u64, "{}", size);
+    }
+
+    #[test]
+    fn apply_rejects_wrong_base() {
+        let dir = TempDir::new().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        fs::write(path("old"), noise(1000, 6)).unwrap();
+        fs::write(path("new"), noise(1000, 7)).unwrap();
+        fs::write(path("other"), noise(999, 8)).unwrap();
+        let job = JobContext::default();
+
+        create_hdiff(&path("old"), &path("new"), &path("diff"), "zstd", &job).unwrap();
+        let err = apply_hdiff(&path("other"), &path("diff"), &path("out"), &job).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::BaseMissing);
+    }
+}
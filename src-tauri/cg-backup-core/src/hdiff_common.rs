@@ -0,0 +1,198 @@
+use crate::checksum::{self, FileHash};
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::manifest;
+use crate::progress::JobContext;
+use crate::utils;
+use std::path::{Path, PathBuf};
+
+/// algo に応じて差分を作成する (hdiff / bsdiff ともにプロセス内で処理する)
+/// 差分の元にした作業ファイルの内容のハッシュを返す
+pub fn create_diff(
+    algo: &str,
+    old_file: &str,
+    new_file: &str,
+    diff_file: &str,
+    compress: &str,
+    job: &JobContext,
+) -> Result<FileHash> {
+    if algo == "bsdiff" {
+        crate::bsdiff::create_bsdiff(old_file, new_file, diff_file, job)
+    } else {
+        crate::hdiff::create_hdiff(old_file, new_file, diff_file, compress, job)
+    }
+}
+
+/// algo と compress の指定で、実際に差分本体に使われる圧縮方式の名前
+/// (bsdiff は常に zlib、hdiff の未知の指定は zstd になる)
+pub fn compressor_name(algo: &str, compress: &str) -> &'static str {
+    if algo == "bsdiff" {
+        "zlib"
+    } else {
+        crate::hdiff::Compressor::from_name(compress).name()
+    }
+}
+
+/// 差分ファイルに対応する .base を特定する
+/// 1. 差分を作ったときに manifest.json / checksum.json へ記録した .base
+/// 2. 記録の無い古い差分は、ファイル名 "名前.ID[.algo].diff" から求めた "名前.base"
+/// 3. それも無ければ、同じフォルダの "作業ファイル名.base"
+pub fn resolve_base_path(work_file: &str, diff_file: &str) -> PathBuf {
+    let diff_path = Path::new(diff_file);
+    let backup_dir = diff_path.parent().unwrap_or_else(|| Path::new("."));
+
+    if let Some(diff) = manifest::find_diff(diff_path) {
+        return backup_dir.join(diff.base);
+    }
+    if let Some(base) = checksum::recorded_base(diff_path) {
+        return backup_dir.join(base.name);
+    }
+
+    let file_name = diff_path
+        .file_name()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    if let Some(stem) = utils::diff_source_name(&file_name) {
+        let base_full = backup_dir.join(format!("{}.base", stem));
+        if base_full.exists() {
+            return base_full;
+        }
+    }
+
+    let work_base_name = format!(
+        "{}.base",
+        Path::new(work_file)
+            .file_name()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default()
+    );
+    backup_dir.join(work_base_name)
+}
+
+// 3. ApplyHdiffWrapper の移植
+pub fn apply_hdiff_wrapper(
+    work_file: &str,
+    diff_file: &str,
+    out_path: &str,
+    hpatchz: Option<&Path>,
+    job: &JobContext,
+) -> Result<()> {
+    let base_full = resolve_base_path(work_file, diff_file);
+    let base_full = base_full.to_string_lossy();
+
+    if crate::hdiff::is_native_hdiff_file(diff_file) {
+        return crate::hdiff::apply_hdiff(&base_full, diff_file, out_path, job);
+    }
+
+    // hpatchz は外部プロセスのため、途中の進捗は取れない
+    job.check()?;
+
+    // hdiffz で作成された旧形式は hpatchz で復元する
+    match hpatchz {
+        Some(bin) => crate::hdiff::apply_hdiff_legacy(bin, &base_full, diff_file, out_path),
+        None => Err(BackupError::new(
+            ErrorKind::Sidecar,
+            "hpatchz is required to restore diffs made by older versions",
+        )
+        .with_path(diff_file)),
+    }
+}
+
+/// bsdiff 形式の差分を適用して out_path に復元する
+pub fn apply_bsdiff_wrapper(
+    work_file: &str,
+    diff_file: &str,
+    out_path: &str,
+    job: &JobContext,
+) -> Result<()> {
+    let base_full = resolve_base_path(work_file, diff_file);
+
+    crate::bsdiff::apply_bsdiff(&base_full.to_string_lossy(), diff_file, out_path, job)
+}
+
+/// 差分ファイルの形式 (manifest の記録 / ファイル名 / ヘッダ) を判別して out_path に復元する
+pub fn apply_diff(
+    work_file: &str,
+    diff_file: &str,
+    out_path: &str,
+    hpatchz: Option<&Path>,
+    job: &JobContext,
+) -> Result<()> {
+    let diff_name = Path::new(diff_file)
+        .file_name()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let recorded_algo = manifest::find_diff(Path::new(diff_file)).map(|d| d.algo);
+
+    if let Some(algo) = recorded_algo {
+        if algo == "bsdiff" {
+            apply_bsdiff_wrapper(work_file, diff_file, out_path, job)
+        } else {
+            apply_hdiff_wrapper(work_file, diff_file, out_path, hpatchz, job)
+        }
+    } else if diff_name.contains(".bsdiff.") {
+        apply_bsdiff_wrapper(work_file, diff_file, out_path, job)
+    } else if diff_name.contains(".hdiff.") {
+        apply_hdiff_wrapper(work_file, diff_file, out_path, hpatchz, job)
+    } else if crate::bsdiff::is_bsdiff_file(diff_file) {
+        // アルゴリズム名を含まない古いファイルはヘッダで判別する
+        apply_bsdiff_wrapper(work_file, diff_file, out_path, job)
+    } else {
+        // 古いファイルのリトライ戦略
+        apply_hdiff_wrapper(work_file, diff_file, out_path, hpatchz, job)
+            .context("recovery failed for old format")
+    }
+}
+
+/// hdiff / bsdiff のテストで共通に使う補助関数
+#[cfg(test)]
+pub(crate) mod test_util {
+    use crate::checksum::{self, FileHash};
+    use crate::error::Result;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// 再現性のある擬似乱数データ (xorshift)
+    pub fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut x = seed | 1;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect()
+    }
+
+    /// old を書き換え・挿入・削除した new を作る (old は 10000 バイト以上)
+    pub fn edited(old: &[u8]) -> Vec<u8> {
+        let mut new = old.to_vec();
+        let len = new.len();
+        new[len / 3..len / 3 + 100].fill(0xAB);
+        new.splice(len / 2..len / 2, noise(5000, 7));
+        new.drain(len / 5..len / 5 + 3000);
+        new.extend_from_slice(b"tail");
+        new
+    }
+
+    /// old -> new の差分を create (old, new, diff) で作り、apply (old, diff, out) で復元する
+    /// 復元結果と、create が返したハッシュが new と一致することを確かめ、差分のサイズを返す
+    pub fn round_trip(
+        old: &[u8],
+        new: &[u8],
+        create: impl FnOnce(&str, &str, &str) -> Result<FileHash>,
+        apply: impl FnOnce(&str, &str, &str) -> Result<()>,
+    ) -> u64 {
+        let dir = TempDir::new().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        fs::write(path("old"), old).unwrap();
+        fs::write(path("new"), new).unwrap();
+
+        let hash = create(&path("old"), &path("new"), &path("diff")).unwrap();
+        assert_eq!(hash, checksum::hash_bytes(new));
+        apply(&path("old"), &path("diff"), &path("out")).unwrap();
+
+        assert_eq!(fs::read(path("out")).unwrap(), new);
+        fs::metadata(path("diff")).unwrap().len()
+    }
+}
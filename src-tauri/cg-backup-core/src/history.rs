@@ -0,0 +1,122 @@
+use crate::error::Result;
+use crate::index::{self, IndexedFile};
+use crate::types::BackupItem;
+use crate::utils;
+use chrono::{DateTime, FixedOffset, Local, TimeZone};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// 作業ファイルに対応するバックアップ履歴を一覧する
+/// ルート直下のフルコピー/アーカイブと、各世代フォルダ (baseN_...) 内の差分を対象とし、作成日時の古い順に返す
+/// 対象は元の作業ファイル名が一致するものだけ (名前の一部が同じ別の作業ファイルのバックアップは含めない)
+pub fn get_backup_list(work_file: &str, backup_dir: &str) -> Result<Vec<BackupItem>> {
+    // --- 1. ルートディレクトリの決定 ---
+    let root = if backup_dir.is_empty() {
+        utils::default_backup_dir(work_file)
+    } else {
+        PathBuf::from(backup_dir)
+    };
+
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let work_name = Path::new(work_file)
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    // --- 2. 索引からルート直下のアーカイブと、世代フォルダ(base*)内の差分を取り出す ---
+    // 索引は変更のあったフォルダだけを走査し直すため、ファイルごとの fs::metadata は不要
+    let mut list = Vec::new();
+    for file in index::load(&root)? {
+        match owner_of(&root, &file) {
+            Some(owner) if utils::same_file_name(&owner, &work_name) => {
+                list.push(create_backup_item(&root, &file, owner));
+            }
+            _ => {}
+        }
+    }
+    Ok(sort_items(list))
+}
+
+/// 共有のバックアップ先フォルダ内のバックアップを、元の作業ファイル名ごとに分けて一覧する
+/// 各一覧は get_backup_list と同じく作成日時の古い順。作業ファイルの分からないファイルは含めない
+pub fn get_backups_by_work_file(backup_dir: &str) -> Result<BTreeMap<String, Vec<BackupItem>>> {
+    let root = PathBuf::from(backup_dir);
+    if !root.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let mut groups: BTreeMap<String, Vec<_>> = BTreeMap::new();
+    for file in index::load(&root)? {
+        let Some(owner) = owner_of(&root, &file) else {
+            continue;
+        };
+        // 大文字小文字を区別しない環境では、最初に見つかった表記にまとめる
+        let key = groups
+            .keys()
+            .find(|k| utils::same_file_name(k, &owner))
+            .cloned()
+            .unwrap_or_else(|| owner.clone());
+        groups
+            .entry(key)
+            .or_default()
+            .push(create_backup_item(&root, &file, owner));
+    }
+    Ok(groups
+        .into_iter()
+        .map(|(owner, list)| (owner, sort_items(list)))
+        .collect())
+}
+
+/// 索引の記録 (manifest の .base 名など) を優先し、古い索引ではファイル名の規則から求める
+fn owner_of(root: &Path, file: &IndexedFile) -> Option<String> {
+    file.owner
+        .clone()
+        .or_else(|| utils::backup_source_name(&file.full_path(root)))
+}
+
+/// 作成日時の古い順に並べる (同時刻ならバックアップ ID、ファイル名の順)
+fn sort_items(mut list: Vec<(DateTime<FixedOffset>, BackupItem)>) -> Vec<BackupItem> {
+    list.sort_by_cached_key(|(created, item)| (*created, item.id.clone(), item.file_name.clone()));
+    list.into_iter().map(|(_, item)| item).collect()
+}
+
+// ヘルパー関数: アイテム生成 (日付フォーマット含む)
+// 作成日時は manifest の記録、名前のバックアップ ID、ファイルの更新日時の順に求める
+// (フォルダを別のドライブへコピーしても、更新日時以外は変わらない)
+fn create_backup_item(
+    root: &Path,
+    file: &IndexedFile,
+    owner: String,
+) -> (DateTime<FixedOffset>, BackupItem) {
+    let modified = Local
+        .timestamp_opt(file.modified, 0)
+        .single()
+        .unwrap_or_else(Local::now)
+        .fixed_offset();
+    let created = DateTime::parse_from_rfc3339(&file.created_at)
+        .ok()
+        .or_else(|| utils::backup_id_time(&file.id).map(|t| t.fixed_offset()))
+        .unwrap_or(modified);
+
+    let item = BackupItem {
+        id: file.id.clone(),
+        file_name: file.file_name().to_string(),
+        file_path: file.full_path(root).to_string_lossy().into_owned(),
+        timestamp: created
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        created_at: created.to_rfc3339(),
+        modified_at: modified.to_rfc3339(),
+        file_size: file.size as i64,
+        generation: file.generation,
+        pinned: file.pin.is_some(),
+        pin_label: file.pin.clone().unwrap_or_default(),
+        note: file.note.clone(),
+        owner,
+    };
+    (created, item)
+}
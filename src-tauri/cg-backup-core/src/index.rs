@@ -0,0 +1,475 @@
+use crate::atomic;
+use crate::checksum;
+use crate::delete;
+use crate::error::{BackupError, Result, ResultExt};
+use crate::manifest;
+use crate::pin;
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// バックアップ先のルートに置く索引のファイル名 (1 行 1 レコードの追記式 JSON)
+pub const INDEX_FILE: &str = "index.jsonl";
+
+/// 更新からこれだけ経っていないフォルダは、同じ時刻のうちに更に変わる可能性があるため
+/// 走査結果を信用しない (タイムスタンプの粗いファイルシステム対策)
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// 生きているレコードに対してこれだけ行数が増えたら、索引を書き直して詰める
+const COMPACT_SLACK: usize = 256;
+
+/// 索引に記録されたバックアップ 1 件
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedFile {
+    /// ルートからの相対パス ("/" 区切り)
+    pub path: String,
+    /// バックアップ ID (20260101_120000_123456、名前に無ければ空)
+    #[serde(default)]
+    pub id: String,
+    pub size: u64,
+    /// 更新日時 (UNIX 秒)
+    pub modified: i64,
+    /// manifest に記録された作成日時 (RFC 3339、記録が無ければ空)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub created_at: String,
+    /// 世代番号 (ルート直下のフルコピー/アーカイブは 0)
+    pub generation: i32,
+    /// ピン留めのラベル (ピン留めされていなければ None)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
+    /// メモ (.note) の内容
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    /// 元の作業ファイル名 (manifest の .base、またはファイル名の規則から。分からなければ None)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+impl IndexedFile {
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    pub fn full_path(&self, root: &Path) -> PathBuf {
+        self.path
+            .split('/')
+            .fold(root.to_path_buf(), |p, c| p.join(c))
+    }
+}
+
+/// index.jsonl の 1 行
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum Record {
+    /// バックアップを追加 / 更新する
+    Add(IndexedFile),
+    /// バックアップ (または世代フォルダ) が無くなった
+    Remove { path: String },
+    /// フォルダ ("" はルート) を走査した時点の更新日時 (ナノ秒)
+    /// None は「走査したが、まだ変わるかもしれないので次回も走査する」
+    Dir { path: String, modified: Option<u64> },
+}
+
+#[derive(Default)]
+struct State {
+    files: BTreeMap<String, IndexedFile>,
+    dirs: BTreeMap<String, Option<u64>>,
+    lines: usize,
+    /// 最後の行が改行で終わっていない (書き込み途中で落ちた)
+    torn: bool,
+}
+
+impl State {
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::Add(file) => {
+                self.files.insert(file.path.clone(), file);
+            }
+            Record::Remove { path } => {
+                self.files.remove(&path);
+                self.dirs.remove(&path);
+            }
+            Record::Dir { path, modified } => {
+                self.dirs.insert(path, modified);
+            }
+        }
+    }
+}
+
+/// ルート内のバックアップを索引から返す
+/// 記録時から更新日時が変わったフォルダだけを走査し直し、差分を索引に追記する
+/// (索引が書き込めなくても、走査結果はそのまま返す)
+pub fn load(root: &Path) -> Result<Vec<IndexedFile>> {
+    sync(root, None)
+}
+
+/// バックアップの作成や、メモ・ピン留めの変更の後に呼び、そのフォルダを索引に反映する
+/// メモの書き換えではフォルダの更新日時が変わらないことがあるため、フォルダを必ず走査し直す
+/// 索引の更新に失敗しても元の操作は成功しているため、警告だけ出す
+pub fn refresh(backup: &Path) {
+    let dir = backup.parent().unwrap_or_else(|| Path::new("."));
+    let (root, rel) = match dir.parent() {
+        Some(parent) if delete::is_generation_dir(dir) => (
+            parent,
+            dir.file_name()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        ),
+        _ => (dir, String::new()),
+    };
+    if let Err(e) = sync(root, Some(&rel)) {
+        log::warn!("Failed to update backup index in {:?}: {}", root, e);
+    }
+}
+
+/// 索引を読み、変更のあったフォルダ (と stale に指定したフォルダ) を反映する
+fn sync(root: &Path, stale: Option<&str>) -> Result<Vec<IndexedFile>> {
+    let mut state = read(root)?;
+    if let Some(rel) = stale {
+        state.dirs.insert(rel.to_string(), None);
+    }
+    let records = reconcile(root, &state)?;
+    if !records.is_empty() {
+        for record in records.iter().cloned() {
+            state.apply(record);
+        }
+        if let Err(e) = write(root, &state, &records) {
+            log::warn!("Failed to update backup index in {:?}: {}", root, e);
+        }
+    }
+    Ok(state.files.into_values().collect())
+}
+
+fn read(root: &Path) -> Result<State> {
+    let mut state = State::default();
+    let path = root.join(INDEX_FILE);
+    let data = match fs::read_to_string(&path) {
+        Ok(d) => d,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(state),
+        Err(e) => return Err(e).at(&path),
+    };
+    state.torn = !data.is_empty() && !data.ends_with('\n');
+    for line in data.lines().filter(|l| !l.trim().is_empty()) {
+        state.lines += 1;
+        match serde_json::from_str::<Record>(line) {
+            Ok(record) => state.apply(record),
+            // 書き込み途中で落ちた行などは読み飛ばす (フォルダの走査で補われる)
+            Err(e) => log::warn!("Skipping broken line in {:?}: {}", path, e),
+        }
+    }
+    Ok(state)
+}
+
+/// 更新日時が記録と違うフォルダを走査し、索引との差分をレコードにして返す
+fn reconcile(root: &Path, state: &State) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    if !root.is_dir() {
+        return Ok(records);
+    }
+
+    // 1. ルート直下 (フルコピー / アーカイブと世代フォルダの一覧)
+    let gen_dirs: BTreeSet<String> = if is_current(root, "", state) {
+        state
+            .dirs
+            .keys()
+            .filter(|d| !d.is_empty())
+            .cloned()
+            .collect()
+    } else {
+        let (files, dirs) = scan_dir(root, "", 0)?;
+        diff_files(state, "", files, &mut records);
+        for gone in state
+            .dirs
+            .keys()
+            .filter(|d| !d.is_empty() && !dirs.contains(*d))
+        {
+            diff_files(state, gone, Vec::new(), &mut records);
+            records.push(Record::Remove { path: gone.clone() });
+        }
+        records.push(dir_record(root, ""));
+        dirs
+    };
+
+    // 2. 世代フォルダ
+    for name in gen_dirs {
+        let dir = root.join(&name);
+        if !dir.is_dir() || is_current(&dir, &name, state) {
+            continue;
+        }
+        let generation = manifest::generation_index(&dir).unwrap_or(0);
+        let (files, _) = scan_dir(&dir, &name, generation)?;
+        diff_files(state, &name, files, &mut records);
+        records.push(dir_record(&dir, &name));
+    }
+
+    Ok(records)
+}
+
+/// フォルダの更新日時が索引の記録と同じなら true
+fn is_current(dir: &Path, rel: &str, state: &State) -> bool {
+    match (state.dirs.get(rel), dir_modified(dir)) {
+        (Some(Some(recorded)), Some(actual)) => *recorded == actual,
+        _ => false,
+    }
+}
+
+fn dir_record(dir: &Path, rel: &str) -> Record {
+    let settled = SystemTime::now()
+        .checked_sub(SETTLE_TIME)
+        .and_then(epoch_nanos);
+    let modified = dir_modified(dir).filter(|&m| settled.is_some_and(|s| m < s));
+    Record::Dir {
+        path: rel.to_string(),
+        modified,
+    }
+}
+
+fn dir_modified(dir: &Path) -> Option<u64> {
+    fs::metadata(dir)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(epoch_nanos)
+}
+
+fn epoch_nanos(t: SystemTime) -> Option<u64> {
+    let d = t.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(d.as_nanos()).ok()
+}
+
+/// フォルダ内のバックアップを読み取る (ルートの場合は世代フォルダ名も返す)
+fn scan_dir(
+    dir: &Path,
+    rel: &str,
+    generation: i32,
+) -> Result<(Vec<IndexedFile>, BTreeSet<String>)> {
+    let mut files = Vec::new();
+    let mut gen_dirs = BTreeSet::new();
+    let is_root = rel.is_empty();
+    let manifest = if is_root {
+        None
+    } else {
+        manifest::load_or_warn(dir)
+    };
+
+    for entry in fs::read_dir(dir).at(dir)?.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let meta = match entry.metadata() {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        if meta.is_dir() {
+            if is_root && delete::is_generation_dir(&path) {
+                gen_dirs.insert(name);
+            }
+            continue;
+        }
+        if !is_backup_name(&name, is_root) {
+            continue;
+        }
+
+        let diff = manifest.as_ref().and_then(|m| m.diff(&name));
+        let note_path = PathBuf::from(format!("{}.note", path.to_string_lossy()));
+        files.push(IndexedFile {
+            path: if is_root {
+                name.clone()
+            } else {
+                format!("{}/{}", rel, name)
+            },
+            id: diff
+                .map(|d| d.id.clone())
+                .or_else(|| utils::parse_backup_id(&name))
+                .unwrap_or_default(),
+            size: meta.len(),
+            modified: meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default(),
+            created_at: diff.map(|d| d.created_at.clone()).unwrap_or_default(),
+            generation,
+            pin: pin::read_pin(&path),
+            note: fs::read_to_string(note_path).unwrap_or_default(),
+            owner: diff
+                .and_then(|d| d.base.strip_suffix(".base").map(str::to_string))
+                .or_else(|| utils::backup_source_name(&path)),
+        });
+    }
+    Ok((files, gen_dirs))
+}
+
+/// 一覧に載せるファイルか (索引・ハッシュ・メモなどの付属ファイルと、書き込み中の一時ファイルを除く)
+fn is_backup_name(name: &str, is_root: bool) -> bool {
+    let sidecar = name.starts_with('.')
+        || name == INDEX_FILE
+        || name == checksum::CHECKSUM_FILE
+        || name == manifest::MANIFEST_FILE
+        || name.ends_with(".note")
+        || name.ends_with(pin::PIN_EXT);
+    // 世代フォルダの .base は差分の元であり、バックアップとしては一覧に出さない
+    !sidecar && (is_root || !name.ends_with(".base"))
+}
+
+/// フォルダ rel の走査結果と索引の記録を比べ、違いをレコードにする
+fn diff_files(state: &State, rel: &str, scanned: Vec<IndexedFile>, records: &mut Vec<Record>) {
+    let in_dir = |path: &str| match path.rsplit_once('/') {
+        Some((dir, _)) => dir == rel,
+        None => rel.is_empty(),
+    };
+    let scanned_paths: BTreeSet<String> = scanned.iter().map(|f| f.path.clone()).collect();
+
+    for path in state.files.keys().filter(|p| in_dir(p)) {
+        if !scanned_paths.contains(path) {
+            records.push(Record::Remove { path: path.clone() });
+        }
+    }
+    for file in scanned {
+        if state.files.get(&file.path) != Some(&file) {
+            records.push(Record::Add(file));
+        }
+    }
+}
+
+/// 新しいレコードを追記する (行数が増えすぎていれば、現在の状態だけで書き直す)
+fn write(root: &Path, state: &State, records: &[Record]) -> Result<()> {
+    let path = root.join(INDEX_FILE);
+    let live = state.files.len() + state.dirs.len();
+    if state.lines + records.len() > live * 2 + COMPACT_SLACK {
+        let mut data = String::new();
+        let all = state
+            .dirs
+            .iter()
+            .map(|(p, m)| Record::Dir {
+                path: p.clone(),
+                modified: *m,
+            })
+            .chain(state.files.values().cloned().map(Record::Add));
+        for record in all {
+            data.push_str(&serde_json::to_string(&record).map_err(BackupError::internal)?);
+            data.push('\n');
+        }
+        return atomic::write_file(&path, |mut file| {
+            file.write_all(data.as_bytes()).at(&path)?;
+            Ok(file)
+        });
+    }
+
+    // 途中で切れた行に続けて書くと、最初のレコードまで読めなくなるため改行を挟む
+    let mut data = if state.torn {
+        String::from("\n")
+    } else {
+        String::new()
+    };
+    for record in records {
+        data.push_str(&serde_json::to_string(record).map_err(BackupError::internal)?);
+        data.push('\n');
+    }
+    // 1 回の write で追記し、他のプロセスの追記と行が混ざらないようにする
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .at(&path)?;
+    file.write_all(data.as_bytes()).at(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::test_util::Fixture;
+    use crate::hdiff_common::test_util::noise;
+
+    fn paths(files: &[IndexedFile]) -> Vec<&str> {
+        let mut paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        paths.sort();
+        paths
+    }
+
+    // 索引に記録されているファイル (走査せずに読む)
+    fn recorded(root: &Path) -> Vec<String> {
+        read(root).unwrap().files.into_keys().collect()
+    }
+
+    #[test]
+    fn entries_whose_file_is_missing_are_removed() {
+        let f = Fixture::new();
+        let kept = f.copy("20240101_120000");
+        let gone = f.copy("20240102_120000");
+        let diff = f.diff(&noise(1000, 1));
+        load(&f.root).unwrap();
+        assert_eq!(recorded(&f.root).len(), 3);
+
+        // 索引を通さずに消す
+        fs::remove_file(&gone).unwrap();
+        fs::remove_dir_all(diff.parent().unwrap()).unwrap();
+        let files = load(&f.root).unwrap();
+
+        assert_eq!(paths(&files), ["work_20240101_120000.clip"]);
+        assert_eq!(recorded(&f.root), ["work_20240101_120000.clip"]);
+        assert!(kept.exists());
+    }
+
+    #[test]
+    fn files_with_no_entry_are_added() {
+        let f = Fixture::new();
+        f.copy("20240101_120000");
+        load(&f.root).unwrap();
+
+        // 索引を通さずに増やす (ルート直下と世代フォルダ)
+        f.copy("20240102_120000");
+        let diff = f.diff(&noise(1000, 1));
+        let diff_path = format!(
+            "{}/{}",
+            diff.parent()
+                .unwrap()
+                .file_name()
+                .unwrap()
+                .to_string_lossy(),
+            diff.file_name().unwrap().to_string_lossy()
+        );
+        let files = load(&f.root).unwrap();
+
+        let expected = [
+            diff_path.as_str(),
+            "work_20240101_120000.clip",
+            "work_20240102_120000.clip",
+        ];
+        assert_eq!(paths(&files), expected);
+        assert_eq!(recorded(&f.root), expected);
+        let added = files.iter().find(|x| x.path == diff_path).unwrap();
+        assert_eq!(added.generation, 1);
+        assert_eq!(added.owner.as_deref(), Some("work.clip"));
+    }
+
+    #[test]
+    fn truncated_last_line_is_skipped_and_not_appended_to() {
+        let f = Fixture::new();
+        f.copy("20240101_120000");
+        load(&f.root).unwrap();
+
+        // 追記の途中で落ちた行
+        let index = f.root.join(INDEX_FILE);
+        let mut file = OpenOptions::new().append(true).open(&index).unwrap();
+        file.write_all(br#"{"op":"add","path":"work_2024"#).unwrap();
+        drop(file);
+        let state = read(&f.root).unwrap();
+        assert!(state.torn);
+        assert_eq!(state.files.len(), 1);
+
+        // 次の追記は改行を挟み、切れた行に続けて書かない
+        f.copy("20240102_120000");
+        load(&f.root).unwrap();
+        let state = read(&f.root).unwrap();
+        assert!(!state.torn);
+        assert_eq!(
+            state.files.into_keys().collect::<Vec<_>>(),
+            ["work_20240101_120000.clip", "work_20240102_120000.clip"]
+        );
+    }
+}
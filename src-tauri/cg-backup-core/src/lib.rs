@@ -0,0 +1,31 @@
+//! WorkBackupTool のバックアップエンジン
+//!
+//! Tauri に依存しない純粋な Rust API として、バックアップ (フル / アーカイブ / 差分)、
+//! 履歴の一覧、復元、検証、世代交代を提供する。
+//! GUI の Tauri コマンドと CLI (wbt) はこのクレートの薄いラッパー。
+pub mod atomic;
+pub mod auto_generation;
+pub mod backup;
+pub mod bsdiff;
+pub mod checksum;
+pub mod delete;
+pub mod error;
+pub mod hdiff;
+pub mod hdiff_common;
+pub mod history;
+pub mod index;
+pub mod manifest;
+pub mod pin;
+pub mod progress;
+pub mod prune;
+pub mod queue;
+pub mod restore;
+pub mod schedule;
+pub mod space;
+pub mod types;
+pub mod utils;
+pub mod verify;
+pub mod watch;
+
+pub use error::{BackupError, ErrorKind, Result};
+pub use types::*;
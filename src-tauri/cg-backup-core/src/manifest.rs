@@ -0,0 +1,258 @@
+use crate::atomic;
+use crate::checksum::{self, FileHash};
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::index;
+use crate::utils;
+use chrono::Local;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 世代フォルダ (baseN_...) ごとに置く、フォルダの中身の説明のファイル名
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// このバージョンより新しい形式の manifest は読まない
+const MANIFEST_VERSION: u32 = 1;
+
+/// manifest に記録するアプリのバージョン
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 世代フォルダ内の .base 1 つ分 (作業ファイルごとに 1 つ)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestBase {
+    pub file_name: String,
+    /// コピー元の作業ファイルのパス
+    pub source_path: String,
+    #[serde(flatten)]
+    pub file: FileHash,
+    /// 作成日時 (RFC 3339)
+    pub created_at: String,
+    pub app_version: String,
+}
+
+/// 世代フォルダ内の差分 1 件分
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestDiff {
+    pub file_name: String,
+    /// バックアップ ID (20260101_120000_123456)
+    pub id: String,
+    /// 作成日時 (RFC 3339)
+    pub created_at: String,
+    /// 元にした .base のファイル名 (同じフォルダ)
+    pub base: String,
+    /// "hdiff" / "bsdiff"
+    pub algo: String,
+    /// 差分本体の圧縮方式 ("zstd" など)
+    pub compressor: String,
+    pub size: u64,
+    /// 復元後 (バックアップ時点の作業ファイル) のサイズ
+    pub source_size: u64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    pub app_version: String,
+}
+
+/// manifest.json の中身
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationManifest {
+    pub version: u32,
+    /// 世代番号 (baseN の N)
+    pub generation: i32,
+    /// 作成日時 (RFC 3339)
+    pub created_at: String,
+    #[serde(default)]
+    pub bases: Vec<ManifestBase>,
+    /// 差分 (作成順)
+    #[serde(default)]
+    pub diffs: Vec<ManifestDiff>,
+}
+
+impl GenerationManifest {
+    fn new(generation: i32) -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            generation,
+            created_at: now(),
+            bases: Vec::new(),
+            diffs: Vec::new(),
+        }
+    }
+
+    pub fn diff(&self, file_name: &str) -> Option<&ManifestDiff> {
+        self.diffs.iter().find(|d| d.file_name == file_name)
+    }
+}
+
+/// フォルダの manifest.json を読み込む (無ければ None)
+pub fn load(dir: &Path) -> Result<Option<GenerationManifest>> {
+    let path = dir.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read_to_string(&path).at(&path)?;
+    let manifest: GenerationManifest = serde_json::from_str(&data).map_err(|e| {
+        BackupError::new(ErrorKind::Corrupt, "Invalid manifest file")
+            .with_path(&path)
+            .with_source(e)
+    })?;
+    if manifest.version > MANIFEST_VERSION {
+        return Err(BackupError::new(
+            ErrorKind::Unsupported,
+            format!("Unsupported manifest version: {}", manifest.version),
+        )
+        .with_path(&path));
+    }
+    Ok(Some(manifest))
+}
+
+/// 一覧や復元で使う版: 読めない manifest は警告だけ出して無いものとして扱う
+/// (呼び出し側はファイル名による推定に切り替える)
+pub fn load_or_warn(dir: &Path) -> Option<GenerationManifest> {
+    load(dir).unwrap_or_else(|e| {
+        log::warn!("Ignoring manifest in {:?}: {}", dir, e);
+        None
+    })
+}
+
+/// 世代番号を返す (manifest が無い古いフォルダはフォルダ名 "baseN_..." から求める)
+pub fn generation_index(dir: &Path) -> Option<i32> {
+    if let Some(manifest) = load_or_warn(dir) {
+        return Some(manifest.generation);
+    }
+    index_from_dir_name(dir)
+}
+
+/// 差分に対応する manifest のエントリ (manifest が無い・記録が無ければ None)
+pub fn find_diff(diff_path: &Path) -> Option<ManifestDiff> {
+    let dir = diff_path.parent().unwrap_or_else(|| Path::new("."));
+    load_or_warn(dir)?.diff(&file_name_of(diff_path)).cloned()
+}
+
+/// バックアップの元の作業ファイル名
+/// .base は名前から、差分は manifest の記録 (元にした .base) を優先し、無ければファイル名の規則から求める
+pub fn source_name(manifest: Option<&GenerationManifest>, path: &Path) -> Option<String> {
+    let file_name = file_name_of(path);
+    if let Some(stem) = file_name.strip_suffix(".base") {
+        return Some(stem.to_string());
+    }
+    manifest
+        .and_then(|m| m.diff(&file_name))
+        .and_then(|d| d.base.strip_suffix(".base").map(str::to_string))
+        .or_else(|| utils::backup_source_name(path))
+}
+
+/// .base を manifest に記録する (同名は上書き)
+/// ハッシュは checksum.json に記録済みならそれを使い、無ければ計算する
+pub fn record_base(dir: &Path, base_path: &Path, source_path: &str) -> Result<()> {
+    let file_name = file_name_of(base_path);
+    let file = match checksum::load(dir)?.and_then(|c| c.files.get(&file_name).cloned()) {
+        Some(entry) => entry.file,
+        None => checksum::hash_file(base_path)?,
+    };
+    let source_path = fs::canonicalize(source_path)
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| source_path.to_string());
+
+    update(dir, |manifest| {
+        manifest.bases.retain(|b| b.file_name != file_name);
+        manifest.bases.push(ManifestBase {
+            file_name,
+            source_path,
+            file,
+            created_at: now(),
+            app_version: APP_VERSION.to_string(),
+        });
+    })
+}
+
+/// 差分を manifest に記録する (同名は上書き)
+pub fn record_diff(dir: &Path, diff: ManifestDiff) -> Result<()> {
+    update(dir, |manifest| {
+        manifest.diffs.retain(|d| d.file_name != diff.file_name);
+        manifest.diffs.push(diff);
+    })
+}
+
+/// 削除したファイルを manifest から取り除く (空になったら manifest.json も消す)
+pub fn forget(dir: &Path, file_names: &[String]) -> Result<()> {
+    let mut manifest = match load(dir)? {
+        Some(m) => m,
+        None => return Ok(()),
+    };
+    manifest
+        .bases
+        .retain(|b| !file_names.contains(&b.file_name));
+    manifest
+        .diffs
+        .retain(|d| !file_names.contains(&d.file_name));
+    if manifest.bases.is_empty() && manifest.diffs.is_empty() {
+        let path = dir.join(MANIFEST_FILE);
+        return fs::remove_file(&path).at(&path);
+    }
+    save(dir, &manifest)
+}
+
+/// バックアップのメモを保存する
+/// メモは従来通り隣の .note に書き、世代フォルダの差分なら manifest にも記録する
+pub fn set_note(backup: &str, note: &str) -> Result<()> {
+    let backup = Path::new(backup);
+    fs::metadata(backup).at(backup)?;
+
+    let note_path = PathBuf::from(format!("{}.note", backup.to_string_lossy()));
+    fs::write(&note_path, note).at(&note_path)?;
+    index::refresh(backup);
+
+    let dir = backup.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = file_name_of(backup);
+    let mut manifest = match load(dir)? {
+        Some(m) => m,
+        None => return Ok(()),
+    };
+    match manifest.diffs.iter_mut().find(|d| d.file_name == file_name) {
+        Some(diff) => diff.note = note.to_string(),
+        None => return Ok(()),
+    }
+    save(dir, &manifest)
+}
+
+fn update(dir: &Path, change: impl FnOnce(&mut GenerationManifest)) -> Result<()> {
+    let mut manifest = match load(dir)? {
+        Some(m) => m,
+        None => GenerationManifest::new(index_from_dir_name(dir).unwrap_or(0)),
+    };
+    change(&mut manifest);
+    save(dir, &manifest)
+}
+
+fn index_from_dir_name(dir: &Path) -> Option<i32> {
+    let re = Regex::new(r"^base(\d+)_").unwrap();
+    let name = dir.file_name()?.to_string_lossy();
+    re.captures(&name)?[1].parse().ok()
+}
+
+/// manifest に記録する現在日時 (RFC 3339)
+pub fn now() -> String {
+    Local::now().to_rfc3339()
+}
+
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn save(dir: &Path, manifest: &GenerationManifest) -> Result<()> {
+    let path = dir.join(MANIFEST_FILE);
+    let data = serde_json::to_string_pretty(manifest).map_err(BackupError::internal)?;
+
+    // 書き込み途中で落ちても既存の記録を壊さないよう、一時ファイル経由で置き換える
+    atomic::write_file(&path, |mut file| {
+        file.write_all(data.as_bytes()).at(&path)?;
+        Ok(file)
+    })
+}
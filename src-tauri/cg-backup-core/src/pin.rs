@@ -0,0 +1,46 @@
+use crate::error::{Result, ResultExt};
+use crate::index;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// ピン留めの印 (バックアップの隣に置く。中身はマイルストーン名)
+pub const PIN_EXT: &str = ".pin";
+
+/// バックアップに対応する .pin のパス
+pub fn pin_path(backup: &Path) -> PathBuf {
+    let mut s = backup.as_os_str().to_owned();
+    s.push(PIN_EXT);
+    PathBuf::from(s)
+}
+
+/// ピン留めされていればラベルを返す (ラベル無しのピンは空文字)
+pub fn read_pin(backup: &Path) -> Option<String> {
+    fs::read_to_string(pin_path(backup))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+pub fn is_pinned(backup: &Path) -> bool {
+    pin_path(backup).exists()
+}
+
+/// バックアップのピン留めを設定 / 解除する
+/// ピン留めされたバックアップは prune や削除の対象にならない
+pub fn set_pinned(backup: &str, pinned: bool, label: &str) -> Result<()> {
+    let backup = Path::new(backup);
+    fs::metadata(backup).at(backup)?;
+
+    let path = pin_path(backup);
+    if pinned {
+        fs::write(&path, label.trim()).at(&path)?;
+    } else {
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            r => r.at(&path)?,
+        }
+    }
+    // ラベルの書き換えはフォルダの更新日時が変わらないことがあるため、索引に知らせる
+    index::refresh(backup);
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use crate::error::{BackupError, ErrorKind, Result};
+use serde::Serialize;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 同じ段階の進捗はこの間隔より細かく通知しない
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 処理の段階
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Phase {
+    /// 差分の計算
+    Diffing,
+    /// 圧縮 (アーカイブ・差分本体) とコピー
+    Compressing,
+    /// 差分の適用・アーカイブの展開
+    Restoring,
+    /// 書き込みの確定とハッシュの記録
+    Finalizing,
+}
+
+/// 進捗 1 回分 (processed / total はバイト数)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    pub phase: Phase,
+    pub processed: u64,
+    pub total: u64,
+}
+
+type ProgressFn = dyn Fn(&Progress) + Send + Sync;
+
+/// 時間のかかる処理に渡す、進捗の通知先と中断フラグ
+/// clone したものは同じジョブを指す (どれから cancel しても止まる)
+#[derive(Clone, Default)]
+pub struct JobContext {
+    cancelled: Arc<AtomicBool>,
+    on_progress: Option<Arc<ProgressFn>>,
+    last_report: Arc<Mutex<Option<(Phase, Instant)>>>,
+}
+
+impl JobContext {
+    /// 進捗を on_progress に通知するジョブ
+    pub fn new(on_progress: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        Self {
+            on_progress: Some(Arc::new(on_progress)),
+            ..Self::default()
+        }
+    }
+
+    /// 中断を要求する (処理は次の確認のタイミングで Cancelled エラーになる)
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// 中断が要求されていればエラーにする
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(cancelled())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// io::Read / io::Write の中で使う版
+    pub fn check_io(&self) -> io::Result<()> {
+        self.check().map_err(io::Error::other)
+    }
+
+    /// 進捗を通知する
+    /// 段階が変わったときと完了時以外は REPORT_INTERVAL ごとに間引く
+    pub fn report(&self, phase: Phase, processed: u64, total: u64) {
+        let on_progress = match &self.on_progress {
+            Some(f) => f,
+            None => return,
+        };
+        {
+            let mut last = self.last_report.lock().unwrap();
+            let due = match *last {
+                Some((p, at)) => {
+                    p != phase || processed >= total || at.elapsed() >= REPORT_INTERVAL
+                }
+                None => true,
+            };
+            if !due {
+                return;
+            }
+            *last = Some((phase, Instant::now()));
+        }
+        on_progress(&Progress {
+            phase,
+            processed,
+            total,
+        });
+    }
+
+    /// 読み込んだバイト数を進捗として通知し、中断要求で止まる Reader
+    pub fn reader<R: Read>(&self, inner: R, phase: Phase, total: u64) -> ProgressReader<'_, R> {
+        ProgressReader {
+            inner,
+            job: self,
+            phase,
+            processed: 0,
+            total,
+        }
+    }
+}
+
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    job: &'a JobContext,
+    phase: Phase,
+    processed: u64,
+    total: u64,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.job.check_io()?;
+        let n = self.inner.read(buf)?;
+        self.processed += n as u64;
+        self.job.report(self.phase, self.processed, self.total);
+        Ok(n)
+    }
+}
+
+/// 中断されたときのエラー
+pub fn cancelled() -> BackupError {
+    BackupError::new(ErrorKind::Cancelled, "Cancelled by user")
+}
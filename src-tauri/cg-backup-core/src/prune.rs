@@ -0,0 +1,329 @@
+use crate::auto_generation;
+use crate::checksum;
+use crate::error::{Result, ResultExt};
+use crate::history;
+use crate::manifest;
+use crate::types::{BackupItem, PruneReport, RetentionPolicy};
+use crate::utils;
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 保持ルールに従って古いバックアップを削除する
+/// dry_run なら何も消さずに、削除対象だけを返す
+/// ピン留めされたバックアップ、残す差分が使う .base、
+/// 次の差分の元になる最新世代の .base は削除しない
+pub fn prune_backups(
+    work_file: &str,
+    backup_dir: &str,
+    policy: &RetentionPolicy,
+    dry_run: bool,
+) -> Result<PruneReport> {
+    let mut items = history::get_backup_list(work_file, backup_dir)?;
+    // 一覧は作成日時の古い順なので、新しい順に並べ替える
+    items.reverse();
+
+    let root = if backup_dir.is_empty() {
+        utils::default_backup_dir(work_file)
+    } else {
+        PathBuf::from(backup_dir.trim_end_matches(['/', '\\']))
+    };
+    let latest_gen = auto_generation::get_latest_generation(&root)?.map(|info| info.dir_path);
+
+    let base_name = format!(
+        "{}.base",
+        Path::new(work_file)
+            .file_name()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default()
+    );
+    let keep = select_kept(&items, policy, &base_name);
+
+    let mut report = PruneReport {
+        dry_run,
+        deleted: Vec::new(),
+        kept: keep.iter().filter(|k| **k).count(),
+        freed_bytes: 0,
+    };
+
+    // 世代フォルダごとに、削除する差分と残る差分があるかをまとめる
+    let mut gen_dirs: BTreeMap<PathBuf, (Vec<String>, bool)> = BTreeMap::new();
+    // バックアップ先直下から削除するフルコピー・アーカイブ
+    let mut root_removed = Vec::new();
+    for (item, kept) in items.iter().zip(&keep) {
+        let path = Path::new(&item.file_path);
+        if item.generation > 0 {
+            if let Some(dir) = path.parent() {
+                let entry = gen_dirs.entry(dir.to_path_buf()).or_default();
+                if *kept {
+                    entry.1 = true;
+                } else {
+                    entry.0.push(item.file_name.clone());
+                }
+            }
+        } else if !kept {
+            root_removed.push(item.file_name.clone());
+        }
+        if !kept {
+            remove(path, dry_run, &mut report)?;
+            // メモ (.note) も一緒に消す
+            let note = PathBuf::from(format!("{}.note", item.file_path));
+            if note.exists() {
+                remove(&note, dry_run, &mut report)?;
+            }
+        }
+    }
+
+    for (dir, (mut removed, has_kept)) in gen_dirs {
+        let is_latest = latest_gen.as_deref() == Some(dir.as_path());
+        if !has_kept && !is_latest {
+            let base = dir.join(&base_name);
+            if base.exists() {
+                remove(&base, dry_run, &mut report)?;
+                removed.push(base_name.clone());
+            }
+        }
+        if dry_run || removed.is_empty() {
+            continue;
+        }
+        checksum::forget(&dir, &removed)?;
+        manifest::forget(&dir, &removed)?;
+
+        // 空になった世代フォルダは片付ける (他の作業ファイルの差分があれば残る)
+        if fs::read_dir(&dir).at(&dir)?.next().is_none() {
+            fs::remove_dir(&dir).at(&dir)?;
+        }
+    }
+    if !dry_run && !root_removed.is_empty() {
+        checksum::forget(&root, &root_removed)?;
+    }
+
+    Ok(report)
+}
+
+/// 残すバックアップを決める (items は新しい順)
+fn select_kept(items: &[BackupItem], policy: &RetentionPolicy, base_name: &str) -> Vec<bool> {
+    if policy.is_unlimited() {
+        return vec![true; items.len()];
+    }
+
+    // 保持ルールがあればそれに当てはまるものだけを、無ければ全件を候補にする
+    let mut keep = vec![!policy.has_keep_rules(); items.len()];
+    for k in keep.iter_mut().take(policy.keep_last as usize) {
+        *k = true;
+    }
+    keep_buckets(items, &mut keep, policy.keep_hourly, "%Y-%m-%d %H");
+    keep_buckets(items, &mut keep, policy.keep_daily, "%Y-%m-%d");
+    keep_buckets(items, &mut keep, policy.keep_weekly, "%G-W%V");
+
+    // 世代数の上限: 新しい世代から max_generations 個だけを残す
+    if policy.max_generations > 0 {
+        let gens: BTreeSet<i32> = items
+            .iter()
+            .map(|i| i.generation)
+            .filter(|g| *g > 0)
+            .collect();
+        let allowed: HashSet<i32> = gens
+            .into_iter()
+            .rev()
+            .take(policy.max_generations as usize)
+            .collect();
+        for (item, k) in items.iter().zip(keep.iter_mut()) {
+            if item.generation > 0 && !allowed.contains(&item.generation) {
+                *k = false;
+            }
+        }
+    }
+
+    // 合計サイズの上限: 新しいものから数えて、超えた分を削除対象にする
+    if policy.max_total_size > 0 {
+        let mut total = 0u64;
+        let mut counted_dirs = HashSet::new();
+        for (item, k) in items.iter().zip(keep.iter_mut()) {
+            if !*k {
+                continue;
+            }
+            let size = stored_size(item, base_name, &mut counted_dirs);
+            if total + size > policy.max_total_size {
+                *k = false;
+            } else {
+                total += size;
+            }
+        }
+    }
+
+    // 最新のバックアップとピン留めされたものは必ず残す
+    if let Some(first) = keep.first_mut() {
+        *first = true;
+    }
+    for (item, k) in items.iter().zip(keep.iter_mut()) {
+        if item.pinned {
+            *k = true;
+        }
+    }
+    keep
+}
+
+/// バックアップが保存先で使っているサイズ (quota の使用量もこれで数える)
+/// 差分は .base とセットで初めて復元できるので、世代ごとに最初の 1 件で .base も数える
+/// counted_dirs には .base を数えた世代フォルダを記録する
+pub(crate) fn stored_size(
+    item: &BackupItem,
+    base_name: &str,
+    counted_dirs: &mut HashSet<PathBuf>,
+) -> u64 {
+    let mut size = item.file_size.max(0) as u64;
+    if let Some(dir) = Path::new(&item.file_path).parent() {
+        if item.generation > 0 && counted_dirs.insert(dir.to_path_buf()) {
+            size += fs::metadata(dir.join(base_name))
+                .map(|m| m.len())
+                .unwrap_or(0);
+        }
+    }
+    size
+}
+
+/// 時間 / 日 / 週ごとに、新しい方から count 区間分の最新 1 件を残す
+fn keep_buckets(items: &[BackupItem], keep: &mut [bool], count: u32, format: &str) {
+    if count == 0 {
+        return;
+    }
+    let mut seen = HashSet::new();
+    for (item, k) in items.iter().zip(keep.iter_mut()) {
+        let key = match DateTime::parse_from_rfc3339(&item.created_at) {
+            Ok(t) => t.with_timezone(&Local).format(format).to_string(),
+            Err(_) => item.timestamp.clone(),
+        };
+        if seen.contains(&key) {
+            continue;
+        }
+        if seen.len() >= count as usize {
+            break;
+        }
+        seen.insert(key);
+        *k = true;
+    }
+}
+
+fn remove(path: &Path, dry_run: bool, report: &mut PruneReport) -> Result<()> {
+    let size = fs::metadata(path).at(path)?.len();
+    if !dry_run {
+        fs::remove_file(path).at(path)?;
+    }
+    report.deleted.push(path.to_string_lossy().into_owned());
+    report.freed_bytes += size;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::test_util::{base_of, Fixture};
+    use crate::hdiff_common::test_util::noise;
+    use crate::pin;
+
+    fn prune(f: &Fixture, policy: RetentionPolicy, dry_run: bool) -> PruneReport {
+        prune_backups(&f.work_file(), &f.backup_dir(), &policy, dry_run).unwrap()
+    }
+
+    fn keep_last(n: u32) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_last: n,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn base_of_a_kept_diff_is_not_deleted() {
+        let f = Fixture::new();
+        let data = noise(200_000, 1);
+        let gen1_old = f.diff(&data);
+        let mut edited = data.clone();
+        edited[100..110].fill(0);
+        let gen1_kept = f.diff(&edited);
+        let gen2 = f.diff(&noise(200_000, 2));
+        let gen1_dir = gen1_kept.parent().unwrap();
+        assert_ne!(gen1_dir, gen2.parent().unwrap());
+
+        let report = prune(&f, keep_last(2), false);
+
+        assert_eq!(report.deleted, [gen1_old.to_string_lossy()]);
+        assert!(gen1_kept.exists() && gen2.exists());
+        assert!(base_of(&gen1_kept).exists());
+        assert!(base_of(&gen2).exists());
+    }
+
+    #[test]
+    fn base_of_an_old_generation_goes_with_its_last_diff() {
+        let f = Fixture::new();
+        let gen1 = f.diff(&noise(200_000, 1));
+        let gen2 = f.diff(&noise(200_000, 2));
+        let gen1_dir = gen1.parent().unwrap().to_path_buf();
+
+        prune(&f, keep_last(1), false);
+
+        assert!(gen2.exists());
+        assert!(!gen1_dir.exists());
+    }
+
+    #[test]
+    fn pinned_backups_are_exempt() {
+        let f = Fixture::new();
+        let oldest = f.copy("20240101_120000");
+        let middle = f.copy("20240102_120000");
+        let newest = f.copy("20240103_120000");
+        pin::set_pinned(&oldest.to_string_lossy(), true, "delivered").unwrap();
+
+        let report = prune(&f, keep_last(1), false);
+
+        assert_eq!(report.deleted, [middle.to_string_lossy()]);
+        assert!(oldest.exists() && newest.exists() && !middle.exists());
+    }
+
+    #[test]
+    fn keep_last_and_age_rules_combine() {
+        let f = Fixture::new();
+        let newest = f.copy("20240110_120000");
+        let same_day = f.copy("20240110_110000");
+        let day_before = f.copy("20240109_120000");
+        let earlier_same_day = f.copy("20240109_090000");
+        let old = f.copy("20240101_120000");
+
+        // keepLast は新しい 2 件、keepDaily は 1 日ごとの最新を 3 日分残す
+        let policy = RetentionPolicy {
+            keep_last: 2,
+            keep_daily: 3,
+            ..Default::default()
+        };
+        let report = prune(&f, policy, false);
+
+        assert_eq!(report.kept, 4);
+        assert_eq!(report.deleted, [earlier_same_day.to_string_lossy()]);
+        for kept in [&newest, &same_day, &day_before, &old] {
+            assert!(kept.exists(), "{:?}", kept);
+        }
+    }
+
+    #[test]
+    fn dry_run_deletes_nothing() {
+        let f = Fixture::new();
+        let copies = [
+            f.copy("20240101_120000"),
+            f.copy("20240102_120000"),
+            f.copy("20240103_120000"),
+        ];
+        let gen1 = f.diff(&noise(200_000, 1));
+        let gen2 = f.diff(&noise(200_000, 2));
+
+        let report = prune(&f, keep_last(1), true);
+
+        assert!(report.dry_run);
+        assert_eq!(report.deleted.len(), 5); // コピー 3 件、gen1 の差分と .base
+        assert!(report.freed_bytes > 0);
+        for path in copies.iter().chain([&gen1, &gen2]) {
+            assert!(path.exists(), "{:?}", path);
+        }
+        assert!(base_of(&gen1).exists());
+    }
+}
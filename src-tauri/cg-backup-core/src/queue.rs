@@ -0,0 +1,280 @@
+use crate::error::Result;
+use crate::progress::{self, JobContext};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+// 待機中に中断要求を確認する間隔
+const WAIT_INTERVAL: Duration = Duration::from_millis(200);
+
+/// キュー内のジョブの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QueueState {
+    /// 同じ作業ファイルの別のジョブが終わるのを待っている
+    Queued,
+    Running,
+}
+
+/// キューに入っているジョブ 1 件 (UI の表示用)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedJob {
+    pub job_id: String,
+    pub work_file: String,
+    /// "backup" / "restore" など
+    pub kind: String,
+    pub state: QueueState,
+}
+
+type ChangeFn = dyn Fn(&[QueuedJob]) + Send + Sync;
+
+struct Inner {
+    jobs: VecDeque<(String, QueuedJob)>, // (作業ファイルのキー, ジョブ) を到着順に持つ
+    next_id: u64,
+}
+
+/// 作業ファイルごとにジョブを直列化するキュー
+/// 同じ作業ファイルのジョブは到着順に 1 件ずつ、別のファイルのジョブは並行して動く
+pub struct JobQueue {
+    inner: Mutex<Inner>,
+    cond: Condvar,
+    on_change: Option<Box<ChangeFn>>,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                jobs: VecDeque::new(),
+                next_id: 0,
+            }),
+            cond: Condvar::new(),
+            on_change: None,
+        }
+    }
+}
+
+impl JobQueue {
+    /// キューの中身が変わるたびに on_change を呼ぶキュー
+    pub fn new(on_change: impl Fn(&[QueuedJob]) + Send + Sync + 'static) -> Self {
+        Self {
+            on_change: Some(Box::new(on_change)),
+            ..Self::default()
+        }
+    }
+
+    /// 作業ファイルの順番が来るまで待ち、実行権 (drop で解放) を返す
+    /// job_id が無い場合 (自動バックアップなど) はキュー側で割り振る
+    /// 待っている間に job が中断されたら Cancelled を返す
+    pub fn acquire(
+        &self,
+        work_file: &str,
+        job_id: Option<String>,
+        kind: &str,
+        job: &JobContext,
+    ) -> Result<QueueTicket<'_>> {
+        let key = work_file_key(work_file);
+        let id = {
+            let mut inner = self.inner.lock().unwrap();
+            let id = job_id.unwrap_or_else(|| {
+                inner.next_id += 1;
+                format!("auto-{}", inner.next_id)
+            });
+            inner.jobs.push_back((
+                key.clone(),
+                QueuedJob {
+                    job_id: id.clone(),
+                    work_file: work_file.to_string(),
+                    kind: kind.to_string(),
+                    state: QueueState::Queued,
+                },
+            ));
+            id
+        };
+        self.notify();
+
+        let mut inner = self.inner.lock().unwrap();
+        loop {
+            // 同じ作業ファイルのジョブのうち、先頭が自分なら実行できる
+            let first = inner
+                .jobs
+                .iter()
+                .position(|(k, _)| *k == key)
+                .expect("queued job disappeared");
+            if inner.jobs[first].1.job_id == id {
+                inner.jobs[first].1.state = QueueState::Running;
+                break;
+            }
+            if job.is_cancelled() {
+                inner.jobs.retain(|(_, j)| j.job_id != id);
+                drop(inner);
+                self.cond.notify_all();
+                self.notify();
+                return Err(progress::cancelled());
+            }
+            inner = self.cond.wait_timeout(inner, WAIT_INTERVAL).unwrap().0;
+        }
+        drop(inner);
+        self.notify();
+
+        Ok(QueueTicket { queue: self, id })
+    }
+
+    /// 現在キューに入っているジョブ (到着順)
+    pub fn snapshot(&self) -> Vec<QueuedJob> {
+        let inner = self.inner.lock().unwrap();
+        inner.jobs.iter().map(|(_, j)| j.clone()).collect()
+    }
+
+    fn release(&self, id: &str) {
+        self.inner
+            .lock()
+            .unwrap()
+            .jobs
+            .retain(|(_, j)| j.job_id != id);
+        self.cond.notify_all();
+        self.notify();
+    }
+
+    fn notify(&self) {
+        if let Some(f) = &self.on_change {
+            f(&self.snapshot());
+        }
+    }
+}
+
+/// 作業ファイルの実行権 (drop すると次のジョブに順番が回る)
+pub struct QueueTicket<'a> {
+    queue: &'a JobQueue,
+    id: String,
+}
+
+impl QueueTicket<'_> {
+    pub fn job_id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Drop for QueueTicket<'_> {
+    fn drop(&mut self) {
+        self.queue.release(&self.id);
+    }
+}
+
+// 同じファイルを別の書き方 (相対パス / 区切り文字の違い) で指定しても同じキーにする
+fn work_file_key(work_file: &str) -> String {
+    let key = fs::canonicalize(Path::new(work_file))
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| work_file.to_string());
+    if cfg!(windows) {
+        key.to_lowercase()
+    } else {
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // ジョブがキューに入る (順番待ちになる) まで待つ
+    fn wait_for_jobs(queue: &JobQueue, count: usize) {
+        let start = std::time::Instant::now();
+        while queue.snapshot().len() < count {
+            assert!(start.elapsed() < TIMEOUT, "job was not queued");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn same_work_file_runs_one_at_a_time() {
+        let queue = JobQueue::default();
+        let job = JobContext::default();
+        let first = queue.acquire("a.clip", None, "backup", &job).unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                let ticket = queue.acquire("a.clip", None, "restore", &job).unwrap();
+                tx.send(ticket.job_id().to_string()).unwrap();
+            });
+
+            wait_for_jobs(&queue, 2);
+            let states: Vec<_> = queue.snapshot().iter().map(|j| j.state).collect();
+            assert_eq!(states, [QueueState::Running, QueueState::Queued]);
+            assert!(rx.recv_timeout(WAIT_INTERVAL * 2).is_err());
+
+            // 先のジョブが終わると順番が回る
+            drop(first);
+            assert_eq!(rx.recv_timeout(TIMEOUT).unwrap(), "auto-2");
+        });
+        assert!(queue.snapshot().is_empty());
+    }
+
+    #[test]
+    fn different_work_files_run_concurrently() {
+        let queue = JobQueue::default();
+        let job = JobContext::default();
+        let (started_tx, started_rx) = mpsc::channel();
+
+        thread::scope(|s| {
+            // 各ジョブは実行権を持ったまま、解放の合図を待つ (直列化されていれば 2 件目が始まらない)
+            let mut releases = Vec::new();
+            for file in ["a.clip", "b.clip"] {
+                let (release_tx, release_rx) = mpsc::channel::<()>();
+                releases.push(release_tx);
+                let (queue, job, started_tx) = (&queue, &job, started_tx.clone());
+                s.spawn(move || {
+                    let _ticket = queue.acquire(file, None, "backup", job).unwrap();
+                    started_tx.send(file).unwrap();
+                    // 送信側が落ちたら (テストの失敗時も) 抜ける
+                    let _ = release_rx.recv();
+                });
+            }
+
+            let mut started = vec![
+                started_rx.recv_timeout(TIMEOUT).unwrap(),
+                started_rx.recv_timeout(TIMEOUT).unwrap(),
+            ];
+            started.sort();
+            assert_eq!(started, ["a.clip", "b.clip"]);
+            assert!(queue
+                .snapshot()
+                .iter()
+                .all(|j| j.state == QueueState::Running));
+            drop(releases);
+        });
+        assert!(queue.snapshot().is_empty());
+    }
+
+    #[test]
+    fn cancelled_while_waiting_leaves_the_queue() {
+        let queue = JobQueue::default();
+        let first = queue
+            .acquire("a.clip", None, "backup", &JobContext::default())
+            .unwrap();
+        let waiting = JobContext::default();
+
+        thread::scope(|s| {
+            let handle = s.spawn(|| {
+                queue
+                    .acquire("a.clip", None, "backup", &waiting)
+                    .map(|t| t.job_id().to_string())
+            });
+            wait_for_jobs(&queue, 2);
+            waiting.cancel();
+            let err = handle.join().unwrap().unwrap_err();
+            assert_eq!(err.kind, crate::error::ErrorKind::Cancelled);
+        });
+        assert_eq!(queue.snapshot().len(), 1);
+        drop(first);
+    }
+}
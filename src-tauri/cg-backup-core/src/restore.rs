@@ -0,0 +1,249 @@
+use crate::atomic;
+use crate::backup;
+use crate::checksum;
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::hdiff_common::apply_diff;
+use crate::progress::{JobContext, Phase};
+use crate::types::{BackupSettings, InPlaceRestore, RestoreOptions};
+use crate::utils;
+use flate2::read::GzDecoder;
+use std::fs::{self, File};
+use std::path::Path;
+use tar::Archive;
+use zip::ZipArchive;
+
+/// 差分ファイルを順番に適用し、options の復元先 (既定は作業ファイルの隣) に別名で復元する
+/// 復元結果はバックアップ時に記録したハッシュと照合し、一致しなければ削除してエラーにする
+/// 成功したら復元したファイルのパスを返す
+pub fn apply_multi_diff(
+    work_file: &str,
+    diff_paths: &[String],
+    options: &RestoreOptions,
+    settings: &BackupSettings,
+    job: &JobContext,
+) -> Result<Vec<String>> {
+    // 復元先のパスを直接指定できるのは 1 件のときだけ
+    if !options.output_path.is_empty() && diff_paths.len() > 1 {
+        return Err(BackupError::new(
+            ErrorKind::InvalidInput,
+            "An output path can only be used when restoring a single backup",
+        ));
+    }
+    let hpatchz = settings.hpatchz_path.as_deref();
+    let mut restored = Vec::new();
+
+    for dp in diff_paths {
+        let out_path = utils::restore_output_path(work_file, dp, options)?;
+        apply_verified(work_file, dp, Path::new(&out_path), hpatchz, job)?;
+        restored.push(out_path);
+    }
+    Ok(restored)
+}
+
+/// 差分 1 件を out_path に復元する
+fn apply_verified(
+    work_file: &str,
+    diff_path: &str,
+    out_path: &Path,
+    hpatchz: Option<&Path>,
+    job: &JobContext,
+) -> Result<()> {
+    restore_verified(diff_path, out_path, job, |temp| {
+        apply_diff(work_file, diff_path, temp, hpatchz, job)
+    })
+}
+
+/// バックアップ 1 件を write で一時ファイルに復元し、記録したハッシュと照合してから out_path に置き換える
+/// 照合が済むまでは一時ファイルのままにし、壊れた復元結果を開いてしまわないようにする
+/// (失敗しても out_path は変わらない)
+fn restore_verified(
+    backup_path: &str,
+    out_path: &Path,
+    job: &JobContext,
+    write: impl FnOnce(&str) -> Result<()>,
+) -> Result<()> {
+    let backup_name = Path::new(backup_path)
+        .file_name()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+
+    let temp = atomic::temp_path(out_path);
+    let result = write(&temp.to_string_lossy())
+        .and_then(|()| {
+            job.check()?;
+            checksum::verify_restored(Path::new(backup_path), &temp)
+        })
+        .and_then(|()| atomic::commit(&temp, out_path));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(e)
+            .at(backup_path)
+            .context(format!("Failed to restore {}", backup_name));
+    }
+    Ok(())
+}
+
+/// 履歴のバックアップ 1 件を options の復元先 (既定は作業ファイルの隣) に別名で復元する
+/// (.diff / .zip / .tar.gz / フルコピーに対応)
+/// 成功したら復元したファイルのパスを返す
+pub fn restore_backup(
+    path: &str,
+    work_file: &str,
+    options: &RestoreOptions,
+    settings: &BackupSettings,
+    job: &JobContext,
+) -> Result<String> {
+    let restored_path = utils::restore_output_path(work_file, path, options)?;
+    restore_to(path, work_file, Path::new(&restored_path), settings, job)?;
+    Ok(restored_path)
+}
+
+/// 履歴のバックアップ 1 件で作業ファイルを直接置き換える
+/// 置き換える前に現在の作業ファイルをフルコピーでバックアップしておくため、
+/// そのコピーを復元すれば元に戻せる。置き換えは一時ファイルからの rename で行う
+pub fn restore_in_place(
+    path: &str,
+    work_file: &str,
+    backup_dir: &str,
+    settings: &BackupSettings,
+    job: &JobContext,
+) -> Result<InPlaceRestore> {
+    // 1. 現在の作業ファイルのスナップショット (前回のコピーと同じなら作らない)
+    let snapshot = if Path::new(work_file).exists() {
+        let outcome = backup::copy_backup_file(work_file, backup_dir, settings, job)
+            .context("Failed to snapshot the work file")?;
+        log::debug!("Snapshot before restore: {:?}", outcome);
+        Some(outcome)
+    } else {
+        None
+    };
+
+    // 2. 作業ファイルを置き換える (失敗しても作業ファイルはそのまま)
+    restore_to(path, work_file, Path::new(work_file), settings, job)?;
+
+    Ok(InPlaceRestore {
+        source: path.to_string(),
+        work_file: work_file.to_string(),
+        snapshot,
+    })
+}
+
+/// バックアップ 1 件を out_path に復元する
+/// (.diff / .zip / .tar.gz / フルコピーに対応)
+/// 復元結果はバックアップ時に記録したハッシュと照合し、一致しなければ削除してエラーにする
+/// 書きかけの復元結果は一時ファイルのまま消えるため、失敗しても out_path は変わらない
+fn restore_to(
+    path: &str,
+    work_file: &str,
+    out_path: &Path,
+    settings: &BackupSettings,
+    job: &JobContext,
+) -> Result<()> {
+    let lower_path = path.to_lowercase();
+
+    // 復元先のフォルダが無ければ作る (一時ファイルを同じフォルダに置くため)
+    if let Some(parent) = out_path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            fs::create_dir_all(parent)
+                .at(parent)
+                .context("Failed to create directory")?;
+        }
+    }
+
+    // 1. 差分パッチ (.diff)
+    if lower_path.ends_with(".diff") {
+        let hpatchz = settings.hpatchz_path.as_deref();
+        return apply_verified(work_file, path, out_path, hpatchz, job);
+    }
+
+    restore_verified(path, out_path, job, |temp| {
+        extract_backup(path, &lower_path, temp, job)
+    })
+}
+
+fn extract_backup(
+    path: &str,
+    lower_path: &str,
+    restored_path: &str,
+    job: &JobContext,
+) -> Result<()> {
+    // 2. ZIPアーカイブ
+    if lower_path.ends_with(".zip") {
+        let file = File::open(path).at(path)?;
+        let mut archive = ZipArchive::new(file).at(path)?;
+        if !archive.is_empty() {
+            let file_in_zip = archive.by_index(0).at(path)?;
+            let size = file_in_zip.size();
+            utils::save_to_work_file(
+                job.reader(file_in_zip, Phase::Restoring, size),
+                restored_path,
+            )?;
+            return Ok(());
+        }
+    }
+
+    // 3. TARアーカイブ (.tar.gz)
+    if utils::is_tar_backup(Path::new(path)) {
+        let file = File::open(path).at(path)?;
+        // 展開後のサイズは分からないため、進捗は圧縮されたままのバイト数で表す
+        let total = file.metadata().at(path)?.len();
+        let tar_gz = GzDecoder::new(job.reader(file, Phase::Restoring, total));
+        let mut archive = Archive::new(tar_gz);
+        if let Some(Ok(mut entry)) = archive.entries().at(path)?.next() {
+            utils::save_to_work_file(&mut entry, restored_path)?;
+            return Ok(());
+        }
+    }
+
+    // 4. フルコピー (.clip / .psd 等)
+    // 既存の utils::copy_file を使用
+    utils::copy_file(path, restored_path, job)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup;
+    use crate::types::BackupOutcome;
+    use tempfile::TempDir;
+
+    // 作業ファイルを mode でバックアップし、作成したバックアップのパスを返す
+    fn backup_as(work_file: &str, backup_dir: &str, mode: &str, format: &str) -> String {
+        let settings = BackupSettings::default();
+        let job = JobContext::default();
+        if mode == "archive" {
+            return backup::archive_backup_file(work_file, backup_dir, format, "", &settings, &job)
+                .unwrap();
+        }
+        match backup::copy_backup_file(work_file, backup_dir, &settings, &job).unwrap() {
+            BackupOutcome::Created { path } => path,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn copy_and_tar_restores_are_checked_against_the_recorded_hash() {
+        let dir = TempDir::new().unwrap();
+        let work = dir.path().join("work.clip");
+        let work_file = work.to_string_lossy().into_owned();
+        let backup_dir = dir.path().join("backups").to_string_lossy().into_owned();
+        fs::write(&work, b"original contents").unwrap();
+
+        let copy = backup_as(&work_file, &backup_dir, "copy", "");
+        let tar = backup_as(&work_file, &backup_dir, "archive", "tar");
+        let settings = BackupSettings::default();
+        let job = JobContext::default();
+        for (i, path) in [&copy, &tar].into_iter().enumerate() {
+            let out = dir.path().join(format!("restored{}.clip", i));
+            restore_to(path, &work_file, &out, &settings, &job).unwrap();
+            assert_eq!(fs::read(&out).unwrap(), b"original contents");
+        }
+
+        // 記録後に書き換わったコピーは復元せず、復元先も作らない
+        fs::write(&copy, b"tampered contents").unwrap();
+        let out = dir.path().join("tampered.clip");
+        let err = restore_to(&copy, &work_file, &out, &settings, &job).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Corrupt);
+        assert!(!out.exists());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::error::Result;
+use crate::types::{BackupOutcome, BackupRequest};
+use crate::watch::{self, Snapshot};
+use chrono::{Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// 定期バックアップの登録内容 (作業ファイルごと)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledBackup {
+    #[serde(flatten)]
+    pub request: BackupRequest,
+    /// 実行間隔 (分)
+    pub interval_minutes: u32,
+    /// この時間帯はバックアップしない
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// バックアップを止める時間帯 ("22:00" 〜 "07:00" のように日付をまたいでもよい)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        let (start, end) = match (parse_time(&self.start), parse_time(&self.end)) {
+            (Some(s), Some(e)) => (s, e),
+            // 読めない設定は無視する (止めずに動かす)
+            _ => return false,
+        };
+        if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").ok()
+}
+
+/// 作業ファイルごとの実行状況
+struct JobState {
+    last_run: Instant,
+    last_backed_up: Option<Snapshot>,
+}
+
+/// 登録された作業ファイルを一定間隔でバックアップするスケジューラ
+/// ウィンドウの表示状態に関係なくバックグラウンドのスレッドで動き、drop すると止まる
+pub struct Scheduler {
+    jobs: Arc<Mutex<Vec<ScheduledBackup>>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    /// tick ごとに期限を迎えたジョブを確認し、run でバックアップする
+    /// 前回のバックアップから作業ファイルが変わっていなければ実行しない
+    pub fn spawn<F>(jobs: Vec<ScheduledBackup>, tick: Duration, mut run: F) -> Self
+    where
+        F: FnMut(&ScheduledBackup) -> Result<BackupOutcome> + Send + 'static,
+    {
+        let jobs = Arc::new(Mutex::new(jobs));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_jobs = jobs.clone();
+        let thread_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            let mut states: HashMap<String, JobState> = HashMap::new();
+
+            while !thread_stop.load(Ordering::Relaxed) {
+                let current = thread_jobs.lock().unwrap().clone();
+                let now = Local::now().time();
+
+                for job in current
+                    .iter()
+                    .filter(|j| j.enabled && j.interval_minutes > 0)
+                {
+                    // 登録直後は 1 間隔待ってから最初のバックアップを取る
+                    let state = states
+                        .entry(job.request.work_file.clone())
+                        .or_insert_with(|| JobState {
+                            last_run: Instant::now(),
+                            last_backed_up: None,
+                        });
+                    let interval = Duration::from_secs(job.interval_minutes as u64 * 60);
+                    if state.last_run.elapsed() < interval {
+                        continue;
+                    }
+                    if job.quiet_hours.as_ref().is_some_and(|q| q.contains(now)) {
+                        continue;
+                    }
+                    state.last_run = Instant::now();
+
+                    let stamp = watch::snapshot(Path::new(&job.request.work_file));
+                    if stamp.is_none() || stamp == state.last_backed_up {
+                        log::debug!(
+                            "Skip scheduled backup (unchanged): {}",
+                            job.request.work_file
+                        );
+                        continue;
+                    }
+                    match run(job) {
+                        Ok(outcome) => {
+                            log::debug!("Scheduled backup: {:?}", outcome);
+                            state.last_backed_up = stamp;
+                        }
+                        Err(e) => log::warn!("Scheduled backup failed: {}", e),
+                    }
+                }
+
+                // 登録を外されたファイルの状態は捨てる
+                states.retain(|k, _| current.iter().any(|j| &j.request.work_file == k));
+
+                // 停止要求にすぐ応えられるよう、短い間隔で寝る
+                let deadline = Instant::now() + tick;
+                while Instant::now() < deadline && !thread_stop.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(200).min(tick));
+                }
+            }
+        });
+
+        Self {
+            jobs,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// 登録内容を差し替える (実行状況は作業ファイル単位で引き継ぐ)
+    pub fn set_jobs(&self, jobs: Vec<ScheduledBackup>) {
+        *self.jobs.lock().unwrap() = jobs;
+    }
+
+    pub fn jobs(&self) -> Vec<ScheduledBackup> {
+        self.jobs.lock().unwrap().clone()
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,213 @@
+use crate::checksum;
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::history;
+use crate::prune;
+use crate::types::{BackupSettings, QuotaAction};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+// 空き容量の確認で見積もりに上乗せする余裕 (見積もりの誤差と checksum.json などの分)
+const FREE_SPACE_MARGIN: u64 = 16 * 1024 * 1024;
+
+/// 保存先のボリュームに required バイト書き込めるだけの空きがあるか確認する
+/// 足りなければ書き込みを始める前に DiskFull を返す
+pub fn ensure_free_space(dir: &Path, required: u64) -> Result<()> {
+    // 保存先がまだ無い場合は、存在する親フォルダのボリュームで調べる
+    let existing = dir.ancestors().find(|p| p.exists()).unwrap_or(dir);
+    let available = fs4::available_space(existing).at(existing)?;
+    let needed = required.saturating_add(FREE_SPACE_MARGIN);
+    if available < needed {
+        return Err(BackupError::new(
+            ErrorKind::DiskFull,
+            format!(
+                "Not enough free space: {} bytes needed, {} bytes available",
+                needed, available
+            ),
+        )
+        .with_path(dir));
+    }
+    Ok(())
+}
+
+/// 差分バックアップで書き込む量を見積もる
+/// .base が無ければ作業ファイルのコピーが必要になる。差分は前回の差分のサイズ
+/// (前回が無ければ作業ファイルの 1 割) を目安にする
+pub fn estimate_diff_size(target_dir: &Path, work_file_name: &str, work_size: u64) -> Result<u64> {
+    let base = target_dir.join(format!("{}.base", work_file_name));
+    let last_diff = checksum::latest_diff(target_dir, work_file_name)?
+        .and_then(|(path, _)| fs::metadata(path).ok())
+        .map(|m| m.len());
+    let diff = last_diff.unwrap_or(work_size / 10);
+    if base.exists() {
+        Ok(diff)
+    } else {
+        Ok(work_size.saturating_add(diff))
+    }
+}
+
+/// 作業ファイルの履歴 (.base を含む) の合計サイズ
+/// prune の合計サイズの上限 (max_total_size) と同じ数え方にする
+pub fn project_usage(work_file: &str, backup_root: &Path) -> Result<u64> {
+    let items = history::get_backup_list(work_file, &backup_root.to_string_lossy())?;
+    let base_name = format!(
+        "{}.base",
+        Path::new(work_file)
+            .file_name()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default()
+    );
+    let mut counted_dirs = HashSet::new();
+    Ok(items
+        .iter()
+        .map(|i| prune::stored_size(i, &base_name, &mut counted_dirs))
+        .sum())
+}
+
+/// expected バイトのバックアップを追加しても、作業ファイルの保存容量の上限 (quota) に収まるか確認する
+/// 収まらない場合、on_exceed が Prune なら保持ルール (retention) に従って古いバックアップを削除し、
+/// それでも足りなければ (または Refuse なら) QuotaExceeded を返す
+pub fn enforce_quota(
+    work_file: &str,
+    backup_root: &Path,
+    expected: u64,
+    settings: &BackupSettings,
+) -> Result<()> {
+    let quota = &settings.quota;
+    if quota.max_bytes == 0 {
+        return Ok(());
+    }
+
+    let mut usage = project_usage(work_file, backup_root)?;
+    if usage.saturating_add(expected) <= quota.max_bytes {
+        return Ok(());
+    }
+
+    if quota.on_exceed == QuotaAction::Prune && expected < quota.max_bytes {
+        // 新しいバックアップの分を空けた上限で prune する
+        let limit = quota.max_bytes - expected;
+        let mut policy = settings.retention.clone();
+        if policy.max_total_size == 0 || policy.max_total_size > limit {
+            policy.max_total_size = limit;
+        }
+        let report =
+            prune::prune_backups(work_file, &backup_root.to_string_lossy(), &policy, false)?;
+        log::info!(
+            "Quota: pruned {} files ({} bytes) from {:?}",
+            report.deleted.len(),
+            report.freed_bytes,
+            backup_root
+        );
+        usage = project_usage(work_file, backup_root)?;
+        if usage.saturating_add(expected) <= quota.max_bytes {
+            return Ok(());
+        }
+    }
+
+    Err(BackupError::new(
+        ErrorKind::QuotaExceeded,
+        format!(
+            "Storage quota exceeded: {} bytes used + {} bytes needed > {} bytes",
+            usage, expected, quota.max_bytes
+        ),
+    )
+    .with_path(backup_root))
+}
+
+/// バックアップを書き込む前の確認 (quota と空き容量)
+pub fn preflight(
+    work_file: &str,
+    backup_root: &Path,
+    target_dir: &Path,
+    expected: u64,
+    settings: &BackupSettings,
+) -> Result<()> {
+    enforce_quota(work_file, backup_root, expected, settings)?;
+    ensure_free_space(target_dir, expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::test_util::{base_of, Fixture};
+    use crate::hdiff_common::test_util::noise;
+    use crate::pin;
+    use crate::types::StorageQuota;
+
+    fn size(path: &Path) -> u64 {
+        fs::metadata(path).unwrap().len()
+    }
+
+    fn quota(max_bytes: u64, on_exceed: QuotaAction) -> BackupSettings {
+        BackupSettings {
+            quota: StorageQuota {
+                max_bytes,
+                on_exceed,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn usage_counts_the_base_of_each_generation() {
+        let f = Fixture::new();
+        let data = noise(200_000, 1);
+        let gen1 = f.diff(&data);
+        let gen1_edit = f.diff(&data[..150_000]);
+        let gen2 = f.diff(&noise(200_000, 2));
+        let copy = f.copy("20240101_120000");
+        assert_eq!(gen1.parent(), gen1_edit.parent());
+        assert_ne!(gen1.parent(), gen2.parent());
+
+        let expected = [&gen1, &gen1_edit, &gen2, &copy]
+            .iter()
+            .map(|p| size(p))
+            .sum::<u64>()
+            + size(&base_of(&gen1))
+            + size(&base_of(&gen2));
+        assert_eq!(project_usage(&f.work_file(), &f.root).unwrap(), expected);
+    }
+
+    #[test]
+    fn refuse_leaves_the_history_alone() {
+        let f = Fixture::new();
+        let copies = [f.copy("20240101_120000"), f.copy("20240102_120000")];
+        let usage = project_usage(&f.work_file(), &f.root).unwrap();
+
+        let settings = quota(usage + 10, QuotaAction::Refuse);
+        enforce_quota(&f.work_file(), &f.root, 10, &settings).unwrap();
+        let err = enforce_quota(&f.work_file(), &f.root, 11, &settings).unwrap_err();
+
+        assert_eq!(err.kind, ErrorKind::QuotaExceeded);
+        assert!(copies.iter().all(|c| c.exists()));
+    }
+
+    #[test]
+    fn prune_frees_old_backups_to_fit() {
+        let f = Fixture::new();
+        let old = f.copy("20240101_120000");
+        let newest = f.copy("20240102_120000");
+
+        let settings = quota(size(&newest) + 10, QuotaAction::Prune);
+        enforce_quota(&f.work_file(), &f.root, 10, &settings).unwrap();
+
+        assert!(!old.exists() && newest.exists());
+    }
+
+    #[test]
+    fn quota_exceeded_when_pruning_cannot_free_enough() {
+        let f = Fixture::new();
+        let pinned = f.copy("20240101_120000");
+        let old = f.copy("20240102_120000");
+        let newest = f.copy("20240103_120000");
+        pin::set_pinned(&pinned.to_string_lossy(), true, "").unwrap();
+
+        // 最新とピン留めは消せないため、それだけで上限を超える
+        let settings = quota(size(&newest) + 10, QuotaAction::Prune);
+        let err = enforce_quota(&f.work_file(), &f.root, 10, &settings).unwrap_err();
+
+        assert_eq!(err.kind, ErrorKind::QuotaExceeded);
+        assert!(!old.exists());
+        assert!(pinned.exists() && newest.exists());
+    }
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// バックアップエンジンの動作設定
+/// (AppConfig からエンジンに必要な値だけを取り出したもの)
+#[derive(Debug, Clone)]
+pub struct BackupSettings {
+    /// 差分サイズが作業ファイルのこの割合を超えたら世代交代する
+    pub auto_base_generation_threshold: f64,
+    /// bsdiff で扱える作業ファイルの上限サイズ (0 なら無制限)
+    pub bsdiff_max_file_size: u64,
+    /// 旧形式 (hdiffz で作成) の差分を復元するための hpatchz のパス
+    pub hpatchz_path: Option<PathBuf>,
+    /// 履歴の保持ルール (prune_backups で使う)
+    pub retention: RetentionPolicy,
+    /// 作業ファイルごとの保存容量の上限
+    pub quota: StorageQuota,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            auto_base_generation_threshold: 0.6,
+            bsdiff_max_file_size: 100_000_000,
+            hpatchz_path: None,
+            retention: RetentionPolicy::default(),
+            quota: StorageQuota::default(),
+        }
+    }
+}
+
+/// バックアップ履歴の保持ルール (AppConfig の retention)
+/// いずれも 0 ならそのルールは使わない。保持ルールが一つも無ければ全件を残し、
+/// max_* の上限だけを適用する
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetentionPolicy {
+    /// 新しい順に N 件を残す
+    pub keep_last: u32,
+    /// 直近 N 時間分、1 時間ごとに最新の 1 件を残す
+    pub keep_hourly: u32,
+    /// 直近 N 日分、1 日ごとに最新の 1 件を残す
+    pub keep_daily: u32,
+    /// 直近 N 週分、1 週ごとに最新の 1 件を残す
+    pub keep_weekly: u32,
+    /// 履歴全体 (.base を含む) の上限サイズ (バイト)
+    pub max_total_size: u64,
+    /// 残す世代フォルダ (baseN_...) の数
+    pub max_generations: u32,
+}
+
+impl RetentionPolicy {
+    /// 何もルールが設定されていない (全件を残す)
+    pub fn is_unlimited(&self) -> bool {
+        !self.has_keep_rules() && self.max_total_size == 0 && self.max_generations == 0
+    }
+
+    /// keep_* のいずれかが設定されている
+    pub fn has_keep_rules(&self) -> bool {
+        self.keep_last > 0 || self.keep_hourly > 0 || self.keep_daily > 0 || self.keep_weekly > 0
+    }
+}
+
+/// 作業ファイル (プロジェクト) ごとの保存容量の上限 (AppConfig の quota)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StorageQuota {
+    /// 1 つの作業ファイルの履歴 (.base を含む) の上限サイズ (バイト)。0 なら無制限
+    pub max_bytes: u64,
+    /// バックアップで上限を超えるときの動作
+    pub on_exceed: QuotaAction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum QuotaAction {
+    /// 保持ルール (retention) に従って古いバックアップを削除し、空きを作る
+    #[default]
+    Prune,
+    /// 何も消さずにバックアップを中止する
+    Refuse,
+}
+
+// prune_backups の結果
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub deleted: Vec<String>, // 削除した (dry-run では削除する) ファイルのパス
+    pub kept: usize,          // 残ったバックアップの件数
+    pub freed_bytes: u64,
+}
+
+/// バックアップ 1 回分の指定 (GUI のタブ設定 / 自動バックアップで共通)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackupRequest {
+    pub work_file: String,
+    /// バックアップ先 (空なら作業ファイルの隣の cg_backup_*)
+    pub backup_dir: String,
+    /// "diff" / "copy" / "archive"
+    pub mode: String,
+    /// 差分アルゴリズム ("hdiff" / "bsdiff")
+    pub algo: String,
+    /// hdiff の圧縮方式
+    pub compress: String,
+    /// アーカイブ形式 ("zip" / "tar")
+    pub format: String,
+    /// ZIP のパスワード
+    pub password: String,
+}
+
+/// バックアップ 1 回分の結果
+/// JS には {status: "created" | "unchanged", path} として渡る
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum BackupOutcome {
+    /// 新しいバックアップを作成した
+    Created { path: String },
+    /// 前回のバックアップから作業ファイルが変わっていないため作成しなかった
+    /// (path は前回のバックアップ)
+    Unchanged { path: String },
+}
+
+impl BackupOutcome {
+    pub fn path(&self) -> &str {
+        match self {
+            BackupOutcome::Created { path } | BackupOutcome::Unchanged { path } => path,
+        }
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, BackupOutcome::Unchanged { .. })
+    }
+}
+
+/// 復元先の指定 (すべて空なら作業ファイルの隣に "{stem}_restored_{id}{ext}" で復元する)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RestoreOptions {
+    /// 復元先のファイルパス (1 件だけ復元する場合。指定すると output_dir / name_template は使わない)
+    pub output_path: String,
+    /// 復元先のフォルダ (空なら作業ファイルと同じフォルダ)
+    pub output_dir: String,
+    /// 復元先のファイル名のテンプレート (空なら "{stem}_restored_{id}{ext}")
+    /// {stem} / {ext} (".clip" のようにドット付き) / {name} は作業ファイル、
+    /// {id} は復元ごとの新しい ID、{backupId} は復元するバックアップの ID に置き換える
+    pub name_template: String,
+}
+
+/// 作業ファイルへの上書き復元 (restore_in_place) の結果
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InPlaceRestore {
+    /// 復元したバックアップ
+    pub source: String,
+    /// 上書きした作業ファイル
+    pub work_file: String,
+    /// 上書き前の作業ファイルのバックアップ (作業ファイルが無かった場合は None)
+    /// 履歴からこれを復元すれば元に戻せる
+    pub snapshot: Option<BackupOutcome>,
+}
+
+// 履歴リストに表示する各ファイルの情報を保持
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupItem {
+    pub id: String, // バックアップ ID (20260101_120000_123456、名前に無ければ空)
+    pub file_name: String,
+    pub file_path: String,
+    pub timestamp: String,   // 作成日時 (表示用 "2026-01-01 12:00:00")
+    pub created_at: String,  // 作成日時 (RFC 3339、manifest かバックアップ ID から)
+    pub modified_at: String, // ファイルの更新日時 (RFC 3339、コピーすると変わる)
+    pub file_size: i64,
+    pub generation: i32,   // 世代番号
+    pub pinned: bool,      // マイルストーンとしてピン留めされているか
+    pub pin_label: String, // ピン留めのラベル ("納品版" など)
+    pub note: String,      // メモ (.note) の内容
+    pub owner: String,     // 元の作業ファイル名 ("test.clip")
+}
+
+// 世代管理を司る構造体 (JSに送らない場合は Serialize 不要ですが、一応付与)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationManager {
+    pub backup_root: String, // cg_backup_元ファイル名/ のパス
+    pub threshold: f64,      // ベース更新の閾値 (例: 0.8 = 80%)
+}
+
+// 現在の世代情報
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupGenInfo {
+    pub dir_path: PathBuf,
+    pub base_idx: i32,
+}
+
+// バックアップ 1 件ごとの検証結果
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyResult {
+    pub file_name: String,
+    pub file_path: String,
+    pub ok: bool,
+    pub status: VerifyStatus,
+    pub message: String, // 失敗時の理由 (成功時は空)
+}
+
+// 検証結果の分類
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum VerifyStatus {
+    Ok,
+    Corrupt,  // 内容が壊れている (checksum 不一致 / 展開失敗)
+    Orphaned, // 差分に対応する .base が無い
+    Missing,  // checksum.json に記録されたファイルが無い
+    Error,    // 上記以外の理由で検証できなかった
+}
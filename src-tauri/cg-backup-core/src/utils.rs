@@ -0,0 +1,540 @@
+use crate::atomic;
+use crate::checksum::{FileHash, HashReader};
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::progress::{JobContext, Phase};
+use crate::types::RestoreOptions;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use regex::Regex;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tar::Archive;
+use tar::Builder;
+use zip::write::SimpleFileOptions;
+use zip::ZipArchive;
+use zip::ZipWriter;
+use zip::{AesMode, CompressionMethod};
+
+// 最後に発行したバックアップ ID (マイクロ秒)。同じ時刻でも ID が重ならないようにする
+static LAST_BACKUP_ID: Mutex<i64> = Mutex::new(0);
+
+/// バックアップ ID を発行する (例: 20260101_120000_123456)
+/// 日時 + 秒未満 6 桁で、文字列のまま並べると作成順になる
+/// 同じマイクロ秒に複数回呼ばれても、前回より必ず大きい ID を返す
+pub fn new_backup_id() -> String {
+    let now = Local::now().timestamp_micros();
+    let micros = {
+        let mut last = LAST_BACKUP_ID.lock().unwrap();
+        *last = now.max(*last + 1);
+        *last
+    };
+    Local
+        .timestamp_micros(micros)
+        .single()
+        .unwrap_or_else(Local::now)
+        .format("%Y%m%d_%H%M%S_%6f")
+        .to_string()
+}
+
+// バックアップの名前の規則 (ID は旧形式の秒単位 20260101_120000 も含む)
+// 差分: "作業ファイル名.ID[.algo].diff"
+const DIFF_NAME: &str = r"^(.+)\.(\d{8}_\d{6}(?:_\d{6})?)(?:\.(?:hdiff|bsdiff))?\.diff$";
+// フルコピー / アーカイブ / 世代フォルダ: "stem[.tar]_ID[.ext]" (timestamped_name の形式)
+const COPY_NAME: &str = r"^(.+?)(\.tar)?_(\d{8}_\d{6}(?:_\d{6})?)(\.[^.]+)?$";
+
+/// ファイル名・フォルダ名からバックアップ ID を取り出す
+/// 名前の規則の決まった位置から読むため、作業ファイル名に含まれる日付は ID とみなさない
+/// 旧形式 (秒単位の 20260101_120000) もそのまま ID として扱う
+pub fn parse_backup_id(name: &str) -> Option<String> {
+    let diff = Regex::new(DIFF_NAME).unwrap();
+    if let Some(caps) = diff.captures(name) {
+        return Some(caps[2].to_string());
+    }
+    let copy = Regex::new(COPY_NAME).unwrap();
+    copy.captures(name).map(|caps| caps[3].to_string())
+}
+
+/// バックアップ ID が表す作成日時 (作成した PC のローカル時刻として読む)
+pub fn backup_id_time(id: &str) -> Option<DateTime<Local>> {
+    let naive = NaiveDateTime::parse_from_str(id, "%Y%m%d_%H%M%S_%6f")
+        .or_else(|_| NaiveDateTime::parse_from_str(id, "%Y%m%d_%H%M%S"))
+        .ok()?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+/// 差分のファイル名 ("作業ファイル名.ID[.algo].diff") から作業ファイル名を取り出す
+/// (test.clip.20251231_150000_123456.hdiff.diff -> test.clip)
+pub fn diff_source_name(diff_name: &str) -> Option<String> {
+    let re = Regex::new(DIFF_NAME).unwrap();
+    re.captures(diff_name).map(|c| c[1].to_string())
+}
+
+/// バックアップのファイル名から、元の作業ファイル名 (拡張子付き) を取り出す
+/// - 差分: "test.clip.ID.algo.diff"
+/// - アーカイブ: "test_ID.zip" / "test.tar_ID.gz" (名前に拡張子が残らないため、中のファイル名を読む)
+/// - フルコピー: "test_ID.clip" (timestamped_name の形式)
+///
+/// 名前の規則に合わないファイルは None
+pub fn backup_source_name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy().into_owned();
+    if name.ends_with(".diff") {
+        return diff_source_name(&name);
+    }
+
+    let re = Regex::new(COPY_NAME).unwrap();
+    let caps = re.captures(&name)?;
+    let stem = &caps[1];
+    let ext = caps.get(4).map(|m| m.as_str()).unwrap_or("");
+
+    // アーカイブの中身は作業ファイル 1 つで、名前の stem がアーカイブ名と一致する
+    let entry = match (caps.get(2).is_some(), ext) {
+        (true, ".gz") => tar_entry_name(path),
+        (false, ".zip") => zip_entry_name(path),
+        _ => None,
+    };
+    if let Some(entry) = entry {
+        if Path::new(&entry).file_stem().map(|s| s.to_string_lossy()) == Some(stem.into()) {
+            return Some(entry);
+        }
+    }
+    let tar = caps.get(2).map(|m| m.as_str()).unwrap_or("");
+    Some(format!("{}{}{}", stem, tar, ext))
+}
+
+/// tar.gz アーカイブのバックアップか
+/// tar_backup_file の作る "test.tar_ID.gz" は名前が .tar.gz で終わらないため、命名規則で判定する
+pub fn is_tar_backup(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if name.ends_with(".tar.gz") {
+        return true;
+    }
+    let re = Regex::new(COPY_NAME).unwrap();
+    re.captures(&name)
+        .is_some_and(|caps| caps.get(2).is_some() && caps.get(4).map(|m| m.as_str()) == Some(".gz"))
+}
+
+fn zip_entry_name(path: &Path) -> Option<String> {
+    let mut archive = ZipArchive::new(File::open(path).ok()?).ok()?;
+    let entry = archive.by_index_raw(0).ok()?;
+    Some(entry.name().to_string())
+}
+
+fn tar_entry_name(path: &Path) -> Option<String> {
+    let mut archive = Archive::new(GzDecoder::new(File::open(path).ok()?));
+    let entry = archive.entries().ok()?.next()?.ok()?;
+    let name = entry.path().ok()?.to_string_lossy().into_owned();
+    Some(name)
+}
+
+/// 作業ファイル名が同じか (Windows はファイル名の大文字小文字を区別しない)
+pub fn same_file_name(a: &str, b: &str) -> bool {
+    if cfg!(windows) {
+        a.to_lowercase() == b.to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+pub fn timestamped_name(original: &str) -> String {
+    let path = Path::new(original);
+
+    // 拡張子を除いたファイル名 (test.clip -> test)
+    let file_stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+
+    // 拡張子 (test.clip -> clip)
+    let extension = path
+        .extension()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+
+    // 重ならないバックアップ ID を付ける
+    let ts = new_backup_id();
+
+    // 拡張子がある場合とない場合で結合を分ける
+    if extension.is_empty() {
+        format!("{}_{}", file_stem, ts)
+    } else {
+        format!("{}_{}.{}", file_stem, ts, extension)
+    }
+}
+
+/// 同梱している外部バイナリ (旧 Sidecar) のパスを返す
+/// Tauri は externalBin を実行ファイルと同じフォルダに配置する
+pub fn sidecar_path(name: &str) -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let dir = exe.parent()?;
+    let file_name = if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    };
+    let path = dir.join(file_name);
+    path.exists().then_some(path)
+}
+
+/// 復元先のファイル名の既定のテンプレート
+pub const DEFAULT_RESTORE_TEMPLATE: &str = "{stem}_restored_{id}{ext}";
+
+/// 作業ファイルの隣に、既定の名前で復元先のパスを作る
+pub fn auto_output_path(work_file: &str) -> String {
+    let path = Path::new(work_file);
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    dir.join(render_restore_name(work_file, "", DEFAULT_RESTORE_TEMPLATE))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// 復元先のパスを決める (RestoreOptions の説明を参照)
+/// source は復元するバックアップ ({backupId} に使う)
+pub fn restore_output_path(
+    work_file: &str,
+    source: &str,
+    options: &RestoreOptions,
+) -> Result<String> {
+    let same_as_work_file = |p: &Path| {
+        let work = Path::new(work_file);
+        p == work
+            || matches!((fs::canonicalize(p), fs::canonicalize(work)), (Ok(a), Ok(b)) if a == b)
+    };
+
+    if !options.output_path.is_empty() {
+        let out = Path::new(&options.output_path);
+        // 作業ファイルの置き換えは、スナップショットを取る restore_in_place に任せる
+        if same_as_work_file(out) {
+            return Err(BackupError::new(
+                ErrorKind::InvalidInput,
+                "Output path is the work file. Use an in-place restore instead",
+            )
+            .with_path(out));
+        }
+        return Ok(options.output_path.clone());
+    }
+
+    let dir = if options.output_dir.is_empty() {
+        Path::new(work_file)
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf()
+    } else {
+        PathBuf::from(&options.output_dir)
+    };
+    let template = if options.name_template.is_empty() {
+        DEFAULT_RESTORE_TEMPLATE
+    } else {
+        &options.name_template
+    };
+
+    let name = render_restore_name(work_file, source, template);
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err(BackupError::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid restore file name template: {}", template),
+        ));
+    }
+    let out = dir.join(&name);
+    // テンプレートに {id} が無いと名前が重なるため、既存のファイルは上書きしない
+    if out.exists() || same_as_work_file(&out) {
+        return Err(BackupError::new(
+            ErrorKind::InvalidInput,
+            "Restore destination already exists",
+        )
+        .with_path(&out));
+    }
+    Ok(out.to_string_lossy().into_owned())
+}
+
+fn render_restore_name(work_file: &str, source: &str, template: &str) -> String {
+    let path = Path::new(work_file);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|s| format!(".{}", s.to_string_lossy()))
+        .unwrap_or_default();
+    let name = path
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let source_name = Path::new(source)
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let backup_id = parse_backup_id(&source_name).unwrap_or_default();
+
+    let mut out = template
+        .replace("{stem}", &stem)
+        .replace("{ext}", &ext)
+        .replace("{name}", &name)
+        .replace("{backupId}", &backup_id);
+    if out.contains("{id}") {
+        out = out.replace("{id}", &new_backup_id());
+    }
+    out
+}
+
+/// デフォルトのバックアップディレクトリを返す
+pub fn default_backup_dir(work_file: &str) -> PathBuf {
+    let path = Path::new(work_file);
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    let file_stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+
+    // cg_backup_ファイル名 フォルダ
+    dir.join(format!("cg_backup_{}", file_stem))
+}
+
+/// 単純なファイルコピーを行う (Go版の CopyFile 相当)
+/// 親ディレクトリの作成、ストリームコピー、ディスク同期(Sync)を網羅
+/// 失敗・中断した場合は書きかけの dst を残さない
+pub fn copy_file(src: &str, dst: &str, job: &JobContext) -> Result<()> {
+    let src_path = Path::new(src);
+    let dst_path = Path::new(dst);
+
+    // 1. 入力ファイルを開く (os.Open)
+    let reader = File::open(src_path)
+        .at(src_path)
+        .context("Failed to open source file")?;
+    let total = reader.metadata().at(src_path)?.len();
+    let mut reader = job.reader(reader, Phase::Compressing, total);
+
+    // 2. 一時ファイルに内容をコピーし (io.Copy)、Sync してから出力先に置き換える
+    atomic::write_file(dst_path, |mut writer| {
+        io::copy(&mut reader, &mut writer)
+            .at(dst_path)
+            .context("Failed to copy file")?;
+        job.report(Phase::Finalizing, total, total);
+        Ok(writer)
+    })
+}
+
+/// 作成したアーカイブのパスと、アーカイブに書き込んだ作業ファイルのハッシュを返す
+/// 失敗・中断した場合は書きかけのアーカイブを残さない
+pub fn zip_backup_file(
+    src: &str,
+    backup_dir: &Path,
+    password: &str,
+    job: &JobContext,
+) -> Result<(PathBuf, FileHash)> {
+    // 1. 保存先の決定 (既存ロジック維持)
+    let stem = Path::new(src)
+        .file_stem()
+        .ok_or_else(|| invalid_name(src))?
+        .to_string_lossy();
+    let zip_filename = timestamped_name(&format!("{}.zip", stem));
+    let zip_path = backup_dir.join(zip_filename);
+
+    let mut source = None;
+    atomic::write_file(&zip_path, |file| {
+        let (file, hash) = write_zip(src, file, &zip_path, password, job)?;
+        source = Some(hash);
+        Ok(file)
+    })?;
+    Ok((zip_path, source.expect("set when the archive was written")))
+}
+
+fn write_zip(
+    src: &str,
+    file: File,
+    zip_path: &Path,
+    password: &str,
+    job: &JobContext,
+) -> Result<(File, FileHash)> {
+    let mut zip = ZipWriter::new(file);
+
+    // 2. オプション構築 (パスワードとAES暗号化を追加)
+    // password引数を使用してAES256モードで暗号化を設定します
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .unix_permissions(0o644)
+        .with_aes_encryption(AesMode::Aes256, password);
+
+    // 3. アーカイブ内にファイルエントリー作成
+    let file_name = Path::new(src)
+        .file_name()
+        .ok_or_else(|| invalid_name(src))?
+        .to_string_lossy();
+    zip.start_file(file_name.to_string(), options)
+        .at(zip_path)?;
+
+    // 4. 内容のコピー (書き込んだ内容のハッシュも取る)
+    let f = File::open(src).at(src)?;
+    let total = f.metadata().at(src)?.len();
+    let mut reader = HashReader::new(job.reader(f, Phase::Compressing, total));
+    io::copy(&mut reader, &mut zip).at(zip_path)?;
+
+    // 5. 書き込み確定
+    job.report(Phase::Finalizing, total, total);
+    let file = zip.finish().at(zip_path)?;
+    Ok((file, reader.finish()))
+}
+
+/// 作成したアーカイブのパスと、アーカイブに書き込んだ作業ファイルのハッシュを返す
+/// 失敗・中断した場合は書きかけのアーカイブを残さない
+pub fn tar_backup_file(
+    src: &str,
+    backup_dir: &Path,
+    job: &JobContext,
+) -> Result<(PathBuf, FileHash)> {
+    let stem = Path::new(src)
+        .file_stem()
+        .ok_or_else(|| invalid_name(src))?
+        .to_string_lossy();
+    let tar_filename = timestamped_name(&format!("{}.tar.gz", stem));
+    let tar_path = backup_dir.join(tar_filename);
+
+    let mut source = None;
+    atomic::write_file(&tar_path, |file| {
+        let (file, hash) = write_tar(src, file, &tar_path, job)?;
+        source = Some(hash);
+        Ok(file)
+    })?;
+    Ok((tar_path, source.expect("set when the archive was written")))
+}
+
+fn write_tar(src: &str, file: File, tar_path: &Path, job: &JobContext) -> Result<(File, FileHash)> {
+    let enc = GzEncoder::new(file, Compression::default());
+    let mut tar = Builder::new(enc);
+
+    let f = File::open(src).at(src)?;
+    let meta = f.metadata().at(src)?;
+
+    // 修正ポイント: file_name を String に変換することで AsRef<Path> を満たすようにする
+    let file_name = Path::new(src)
+        .file_name()
+        .ok_or_else(|| invalid_name(src))?
+        .to_string_lossy()
+        .into_owned(); // ここで String (owned data) に変換
+
+    // 進捗を取るため append_file ではなく、ヘッダを作って Reader から書き込む
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata(&meta);
+    let total = meta.len();
+    let mut reader = HashReader::new(job.reader(f, Phase::Compressing, total));
+    tar.append_data(&mut header, &file_name, &mut reader)
+        .at(tar_path)?;
+
+    job.report(Phase::Finalizing, total, total);
+    let file = tar.into_inner().and_then(|enc| enc.finish()).at(tar_path)?;
+    Ok((file, reader.finish()))
+}
+
+/// Readerの内容をターゲットファイルに書き出す (Goの saveToWorkFile 相当)
+/// Rustでは io::Read トレイトを持つものを引数に取ります
+/// 一時ファイルに書いてから置き換えるため、途中で落ちても既存のファイルは壊れない
+pub fn save_to_work_file<R: Read>(mut reader: R, target_file: &str) -> Result<()> {
+    atomic::write_file(Path::new(target_file), |mut out| {
+        io::copy(&mut reader, &mut out)
+            .at(target_file)
+            .context("Failed to copy data")?;
+        Ok(out)
+    })
+}
+
+pub fn restore_archive(archive_path: &str, work_file: &str) -> Result<()> {
+    let path = Path::new(archive_path);
+    let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+
+    if file_name.ends_with(".zip") {
+        let file = File::open(archive_path).at(archive_path)?;
+        let mut archive = ZipArchive::new(file).at(archive_path)?;
+
+        if !archive.is_empty() {
+            let mut file_in_zip = archive.by_index(0).at(archive_path)?;
+            // 既存の utils 関数を呼び出し
+            save_to_work_file(&mut file_in_zip, work_file)?;
+            return Ok(());
+        }
+    } else if file_name.ends_with(".tar.gz") {
+        let file = File::open(archive_path).at(archive_path)?;
+        let tar_gz = GzDecoder::new(file);
+        let mut archive = Archive::new(tar_gz);
+
+        if let Some(Ok(mut entry)) = archive.entries().at(archive_path)?.next() {
+            // 既存の utils 関数を呼び出し
+            save_to_work_file(&mut entry, work_file)?;
+            return Ok(());
+        }
+    }
+
+    Err(BackupError::new(
+        ErrorKind::Unsupported,
+        "Unsupported archive format or the archive is empty",
+    )
+    .with_path(archive_path))
+}
+
+fn invalid_name(path: &str) -> BackupError {
+    BackupError::new(ErrorKind::InvalidInput, "Invalid file name").with_path(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_backup_id_reads_the_id_position() {
+        let id = "20260101_120000_123456";
+        for name in [
+            "test.clip.20260101_120000_123456.hdiff.diff",
+            "test_20260101_120000_123456.clip",
+            "test_20260101_120000_123456.zip",
+            "test.tar_20260101_120000_123456.gz",
+            "base1_20260101_120000_123456",
+            // 作業ファイル名に日付が含まれていても ID と取り違えない
+            "20250101_20260101_120000_123456.clip",
+            "shot_20250101_120000.clip.20260101_120000_123456.diff",
+            "shot_20250101_120000_20260101_120000_123456.clip",
+        ] {
+            assert_eq!(parse_backup_id(name).as_deref(), Some(id), "{}", name);
+        }
+        assert_eq!(
+            parse_backup_id("test.clip.20260101_120000.diff").as_deref(),
+            Some("20260101_120000")
+        );
+        assert_eq!(parse_backup_id("20250101_20260101_120000.clip.base"), None);
+        assert_eq!(parse_backup_id("test.clip"), None);
+    }
+
+    #[test]
+    fn backup_source_name_follows_the_naming_grammar() {
+        let source = |name: &str| backup_source_name(Path::new(name));
+        assert_eq!(
+            source("cat.clip.20260101_120000_123456.hdiff.diff").as_deref(),
+            Some("cat.clip")
+        );
+        assert_eq!(
+            source("concat_20260101_120000_123456.clip").as_deref(),
+            Some("concat.clip")
+        );
+        assert_eq!(
+            source("shot_20250101_120000_20260101_120000.clip").as_deref(),
+            Some("shot_20250101_120000.clip")
+        );
+        assert_eq!(source("cat.clip.base"), None);
+    }
+
+    #[test]
+    fn is_tar_backup_matches_tar_backup_names() {
+        let tar = |name: &str| is_tar_backup(Path::new(name));
+        assert!(tar("test.tar_20260101_120000_123456.gz"));
+        assert!(tar("old.tar.gz"));
+        assert!(!tar("test_20260101_120000_123456.gz"));
+        assert!(!tar("test_20260101_120000_123456.zip"));
+        assert!(!tar("test.clip.20260101_120000_123456.hdiff.diff"));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::atomic;
+use crate::checksum::{self, ChecksumEntry, FileHash};
+use crate::error::{BackupError, ErrorKind, Result, ResultExt};
+use crate::hdiff_common::{apply_diff, resolve_base_path};
+use crate::history;
+use crate::progress::JobContext;
+use crate::types::{BackupSettings, VerifyResult, VerifyStatus};
+use crate::utils;
+use flate2::read::GzDecoder;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use tar::Archive;
+use zip::ZipArchive;
+
+/// 作業ファイルのバックアップ履歴をすべて検証する
+/// 差分は一時ファイルへ実際に復元し、アーカイブ/フルコピーは最後まで読み切れるかを確認する
+/// 世代フォルダやバックアップ先直下に checksum.json があれば、記録されたハッシュとも照合する
+pub fn verify_backups(
+    work_file: &str,
+    backup_dir: &str,
+    settings: &BackupSettings,
+) -> Result<Vec<VerifyResult>> {
+    let items = history::get_backup_list(work_file, backup_dir)?;
+
+    let mut gen_dirs = BTreeSet::new();
+    let mut results = Vec::new();
+    for item in items {
+        if item.generation > 0 {
+            if let Some(dir) = Path::new(&item.file_path).parent() {
+                gen_dirs.insert(dir.to_path_buf());
+            }
+        }
+
+        let result = verify_backup(&item.file_path, work_file, settings);
+        let status = match &result {
+            Ok(()) => VerifyStatus::Ok,
+            Err(e) => status_of(e),
+        };
+        results.push(VerifyResult {
+            file_name: item.file_name,
+            file_path: item.file_path,
+            ok: result.is_ok(),
+            status,
+            message: result.err().map(|e| e.to_string()).unwrap_or_default(),
+        });
+    }
+
+    // checksum.json に記録されているのに消えているファイルも報告する
+    for dir in gen_dirs {
+        results.extend(missing_files(&dir));
+    }
+
+    Ok(results)
+}
+
+/// バックアップ 1 件を検証する (作業ファイルや履歴には一切書き込まない)
+pub fn verify_backup(path: &str, work_file: &str, settings: &BackupSettings) -> Result<()> {
+    let lower_path = path.to_lowercase();
+
+    // 1. 差分: 一時ファイルに復元できるか
+    if lower_path.ends_with(".diff") {
+        let file_name = Path::new(path)
+            .file_name()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default();
+        let base = resolve_base_path(work_file, path);
+        if !base.exists() {
+            return Err(BackupError::new(
+                ErrorKind::BaseMissing,
+                "Base file for this diff was not found",
+            )
+            .with_path(&base));
+        }
+
+        // checksum.json があれば、差分と .base が記録時から変わっていないか確認する
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+        let checksums = checksum::load(dir)?.unwrap_or_default();
+        if let Some(entry) = checksums.files.get(file_name.as_ref()) {
+            check_hash(Path::new(path), entry)?;
+        }
+        // .base は差分を作った時点のハッシュ (記録の無い古い差分は .base 自身の記録) と照合する
+        let base_hash = match checksums
+            .files
+            .get(file_name.as_ref())
+            .and_then(|e| e.base.as_ref())
+        {
+            Some(base_ref) => Some(&base_ref.file),
+            None => base
+                .file_name()
+                .and_then(|n| checksums.files.get(n.to_string_lossy().as_ref()))
+                .map(|e| &e.file),
+        };
+        if let Some(expected) = base_hash {
+            check_file_hash(&base, expected)?;
+        }
+
+        // 差分の隣の一意な一時ファイルに復元する (同時に走る検証とぶつからず、残っても sweep_stale が消す)
+        let temp_out = atomic::temp_path(Path::new(path));
+        let temp_str = temp_out.to_string_lossy();
+
+        let result = apply_diff(
+            work_file,
+            path,
+            &temp_str,
+            settings.hpatchz_path.as_deref(),
+            &JobContext::default(),
+        )
+        .and_then(|()| checksum::verify_restored(Path::new(path), &temp_out));
+        let _ = fs::remove_file(&temp_out);
+        return result;
+    }
+
+    // checksum.json に記録があれば (フルコピー・アーカイブ)、記録時から変わっていないか確認する
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if let Some(entry) = checksum::load(dir)?.and_then(|c| c.files.get(&file_name).cloned()) {
+        check_hash(Path::new(path), &entry)?;
+    }
+
+    // 2. ZIPアーカイブ: 暗号化されていなければ展開して CRC を確認する
+    if lower_path.ends_with(".zip") {
+        let file = File::open(path).at(path)?;
+        let mut archive = ZipArchive::new(file).at(path)?;
+        if archive.is_empty() {
+            return Err(empty_archive(path));
+        }
+        let encrypted = archive.by_index_raw(0).at(path)?.encrypted();
+        if !encrypted {
+            let mut entry = archive.by_index(0).at(path)?;
+            io::copy(&mut entry, &mut io::sink()).at(path)?;
+        }
+        return Ok(());
+    }
+
+    // 3. TARアーカイブ (.tar.gz): 最後まで展開できるか
+    if utils::is_tar_backup(Path::new(path)) {
+        let file = File::open(path).at(path)?;
+        let mut archive = Archive::new(GzDecoder::new(file));
+        let mut count = 0;
+        for entry in archive.entries().at(path)? {
+            let mut entry = entry.at(path)?;
+            io::copy(&mut entry, &mut io::sink()).at(path)?;
+            count += 1;
+        }
+        if count == 0 {
+            return Err(empty_archive(path));
+        }
+        return Ok(());
+    }
+
+    // 4. フルコピー: 読み込めるか
+    let mut f = File::open(path).at(path)?;
+    io::copy(&mut f, &mut io::sink()).at(path)?;
+    Ok(())
+}
+
+/// ファイルのハッシュが checksum.json の記録と一致するか確認する
+fn check_hash(path: &Path, entry: &ChecksumEntry) -> Result<()> {
+    check_file_hash(path, &entry.file)
+}
+
+fn check_file_hash(path: &Path, expected: &FileHash) -> Result<()> {
+    let actual = checksum::hash_file(path)?;
+    if actual != *expected {
+        return Err(BackupError::new(ErrorKind::Corrupt, "Checksum mismatch").with_path(path));
+    }
+    Ok(())
+}
+
+/// checksum.json に記録されているが実体の無いファイルを列挙する
+fn missing_files(dir: &Path) -> Vec<VerifyResult> {
+    let checksums = match checksum::load(dir) {
+        Ok(Some(c)) => c,
+        Ok(None) => return vec![],
+        Err(e) => {
+            let path = dir.join(checksum::CHECKSUM_FILE);
+            return vec![VerifyResult {
+                file_name: checksum::CHECKSUM_FILE.to_string(),
+                file_path: path.to_string_lossy().into_owned(),
+                ok: false,
+                status: status_of(&e),
+                message: e.to_string(),
+            }];
+        }
+    };
+
+    checksums
+        .files
+        .keys()
+        .map(|name| dir.join(name))
+        .filter(|path| !path.exists())
+        .map(|path: PathBuf| VerifyResult {
+            file_name: path
+                .file_name()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            file_path: path.to_string_lossy().into_owned(),
+            ok: false,
+            status: VerifyStatus::Missing,
+            message: "Recorded in checksum.json but not found".to_string(),
+        })
+        .collect()
+}
+
+fn status_of(e: &BackupError) -> VerifyStatus {
+    match e.kind {
+        ErrorKind::BaseMissing => VerifyStatus::Orphaned,
+        ErrorKind::Corrupt => VerifyStatus::Corrupt,
+        ErrorKind::NotFound => VerifyStatus::Missing,
+        _ => VerifyStatus::Error,
+    }
+}
+
+fn empty_archive(path: &str) -> BackupError {
+    BackupError::new(ErrorKind::Corrupt, "Archive is empty").with_path(path)
+}
@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+/// 監視の設定
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// ファイルの状態を確認する間隔
+    pub poll_interval: Duration,
+    /// サイズと更新日時がこの時間変わらなければ保存完了とみなす
+    pub settle_time: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            settle_time: Duration::from_secs(3),
+        }
+    }
+}
+
+/// 作業ファイルの保存を検知してコールバックを呼ぶ監視スレッド
+/// 保存途中の .clip / .psd を拾わないよう、サイズと更新日時が落ち着くまで待つ
+/// drop すると監視を止める
+pub struct FileWatcher {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl FileWatcher {
+    /// 監視を開始する (開始時点の状態は「保存済み」として扱う)
+    pub fn spawn<F>(path: impl AsRef<Path>, options: WatchOptions, mut on_saved: F) -> Self
+    where
+        F: FnMut(&Path) + Send + 'static,
+    {
+        let path = path.as_ref().to_path_buf();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_path = path.clone();
+        let thread_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            let mut last_saved = snapshot(&thread_path);
+            let mut pending: Option<(Snapshot, Instant)> = None;
+
+            while !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(options.poll_interval);
+
+                // 保存中に一時的に消える (置き換え保存) 場合は次の確認まで待つ
+                let current = match snapshot(&thread_path) {
+                    Some(s) => s,
+                    None => {
+                        pending = None;
+                        continue;
+                    }
+                };
+                if Some(current) == last_saved {
+                    pending = None;
+                    continue;
+                }
+
+                match pending {
+                    Some((s, since)) if s == current => {
+                        if since.elapsed() >= options.settle_time {
+                            log::debug!("File settled: {:?}", thread_path);
+                            on_saved(&thread_path);
+                            last_saved = Some(current);
+                            pending = None;
+                        }
+                    }
+                    // 変化が続いている間は待ち直す
+                    _ => pending = Some((current, Instant::now())),
+                }
+            }
+        });
+
+        Self {
+            path,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 監視を止める (実行中のコールバックは最後まで走る)
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// ファイルのサイズと更新日時 (変更検知用)
+pub type Snapshot = (u64, Option<SystemTime>);
+
+pub fn snapshot(path: &Path) -> Option<Snapshot> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.len(), meta.modified().ok()))
+}
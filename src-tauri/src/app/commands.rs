@@ -3,29 +3,25 @@ use std::fs;
 use std::path::{Path, PathBuf};
 
 // 外部クレート
-use chrono::{DateTime, Local};
-use tauri::{AppHandle, LogicalSize, Manager, Size, State, WebviewWindow, Window};
+use tauri::{AppHandle, Emitter, LogicalSize, Manager, Size, State, WebviewWindow, Window};
 
 // Tauriプラグイン
 use tauri_plugin_dialog::DialogExt;
 use tauri_plugin_shell::ShellExt;
 
 // 内部モジュール (自作)
-use crate::app::hdiff_common::*;
 use crate::app::state::AppState;
-use crate::app::types::BackupItem;
 use crate::app::types::*;
-use crate::app::{auto_generation, utils};
-use flate2::read::GzDecoder;
-use regex::Regex;
-use std::collections::HashMap;
-use std::fs::File;
-use tar::Archive;
-use zip::ZipArchive;
+use crate::app::utils;
+use cg_backup_core::error::{ErrorKind, ResultExt};
+use cg_backup_core::progress::JobContext;
+use cg_backup_core::watch::{FileWatcher, WatchOptions};
+use cg_backup_core::{atomic, backup, delete, history, manifest, pin, prune, restore, verify};
+use std::collections::{BTreeMap, HashMap};
 
 #[tauri::command]
-pub fn get_config(state: State<'_, AppState>) -> Result<AppConfig, String> {
-    let cfg = state.config.lock().map_err(|e| e.to_string())?;
+pub fn get_config(state: State<'_, AppState>) -> Result<AppConfig, BackupError> {
+    let cfg = state.config.lock().map_err(BackupError::internal)?;
     Ok(cfg.clone())
 }
 
@@ -34,18 +30,20 @@ pub fn set_always_on_top(
     window: Window,
     state: State<'_, AppState>,
     flag: bool,
-) -> Result<(), String> {
+) -> Result<(), BackupError> {
     // 1. ウィンドウの設定変更
     #[cfg(desktop)]
     {
-        window.set_always_on_top(flag).map_err(|e| e.to_string())?;
+        window
+            .set_always_on_top(flag)
+            .map_err(BackupError::internal)?;
     }
     // 2. 設定の保存
     {
         let mut cfg = state.config.lock().unwrap();
         cfg.always_on_top = flag;
     }
-    state.save().map_err(|e| e.to_string())?;
+    state.save()?;
     Ok(())
 }
 
@@ -59,11 +57,16 @@ pub fn get_auto_base_generation_threshold(state: State<'_, AppState>) -> f64 {
     state.config.lock().unwrap().auto_base_generation_threshold
 }
 
+#[tauri::command]
+pub fn get_bsdiff_max_file_size(state: State<'_, AppState>) -> u64 {
+    state.config.lock().unwrap().bsdiff_max_file_size
+}
+
 /// 特定のキーに対応する翻訳テキストを返す (Goの GetLanguageText 相当)
 /// Rust内部のメニュー構築などで使用する場合、AppStateを引数に取る形で実装
 
 #[tauri::command]
-pub fn get_language_text(state: State<'_, AppState>, key: &str) -> Result<String, String> {
+pub fn get_language_text(state: State<'_, AppState>, key: &str) -> Result<String, BackupError> {
     let cfg = state.config.lock().unwrap();
     let lang = if cfg.language.is_empty() {
         "ja"
@@ -83,7 +86,7 @@ pub fn get_language_text(state: State<'_, AppState>, key: &str) -> Result<String
 
 /// 現在の言語設定に基づいた辞書をまるごと返す (Goの GetI18N 相当)
 #[tauri::command]
-pub fn get_i18n(state: State<'_, AppState>) -> Result<HashMap<String, String>, String> {
+pub fn get_i18n(state: State<'_, AppState>) -> Result<HashMap<String, String>, BackupError> {
     let cfg = state.config.lock().unwrap();
     let lang = if cfg.language.is_empty() {
         "ja"
@@ -96,13 +99,13 @@ pub fn get_i18n(state: State<'_, AppState>) -> Result<HashMap<String, String>, S
 
 /// 言語を切り替えて保存する (Goの SetLanguage 相当)
 #[tauri::command]
-pub fn set_language(state: State<'_, AppState>, lang: String) -> Result<(), String> {
+pub fn set_language(state: State<'_, AppState>, lang: String) -> Result<(), BackupError> {
     {
         let mut cfg = state.config.lock().unwrap();
         cfg.language = lang;
     }
     // 前に作った state.save() を呼び出す
-    state.save().map_err(|e| e.to_string())?;
+    state.save()?;
     Ok(())
 }
 
@@ -113,194 +116,14 @@ pub async fn backup_or_diff(
     custom_dir: String,
     algo: String,
     compress: String,
-) -> Result<(), String> {
-    use crate::app::state::AppState;
-    use regex::Regex;
-    use std::fs;
-    use std::path::{Path, PathBuf};
-    use tauri::Manager;
-
-    // --- 1. ディレクトリの決定 ---
-    // Linux/WSL2での末尾スラッシュ問題を避けるため、一旦trimしてPathBufを作成
-    let initial_path = if custom_dir.is_empty() {
-        utils::default_backup_dir(&work_file)
-    } else {
-        PathBuf::from(custom_dir.trim_end_matches(|c| c == '/' || c == '\\'))
-    };
-
-    let mut target_dir: PathBuf;
-    let mut current_idx: i32 = 0;
-    let project_root: PathBuf;
-
-    // 確実に「最後のフォルダ名」を取得するための堅牢な方法
-    let folder_name = initial_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("");
-
-    println!(
-        "DEBUG: STEP1 - initial_path={:?}, folder_name='{}'",
-        initial_path, folder_name
-    );
-
-    // --- 1a. 手動選択された世代フォルダか、親フォルダかの判定 ---
-    if folder_name.starts_with("base") {
-        println!("DEBUG: STEP2 - Entering MANUAL route");
-        // A. 特定の世代フォルダ (.../baseN) が直接指定されている場合
-        target_dir = initial_path.clone();
-
-        // 親ディレクトリを「世代交代の起点」として保持する
-        project_root = initial_path
-            .parent()
-            .map(|p| p.to_path_buf())
-            .unwrap_or_else(|| initial_path.clone());
-
-        // Regexでインデックスを抽出
-        let re_idx = Regex::new(r"base(\d+)").unwrap();
-        if let Some(caps) = re_idx.captures(folder_name) {
-            current_idx = caps[1].parse().unwrap_or(0);
-        }
-        println!("DEBUG: Manual Index identified as {}", current_idx);
-    } else {
-        println!(
-            "DEBUG: STEP2 - Entering AUTO route (via parent dir: '{}')",
-            folder_name
-        );
-        // B. 親フォルダが指定されている場合
-        project_root = initial_path.clone();
-        let (resolved_path, idx) =
-            auto_generation::resolve_generation_dir(&project_root, &work_file)?;
-        target_dir = resolved_path;
-        current_idx = idx;
-        println!(
-            "DEBUG: Auto Resolved Path={:?}, idx={}",
-            target_dir, current_idx
-        );
-    }
-
-    // フォルダの存在保証
-    if !target_dir.exists() {
-        fs::create_dir_all(&target_dir).map_err(|e| e.to_string())?;
-    }
-
-    let file_name = Path::new(&work_file)
-        .file_name()
-        .ok_or("Invalid work file name")?
-        .to_string_lossy();
-    let base_full = target_dir.join(format!("{}.base", file_name));
-
-    // --- 2. .baseファイルの同期 ---
-    if !base_full.exists() {
-        fs::copy(&work_file, &base_full).map_err(|e| format!("Failed to sync base file: {}", e))?;
-    }
-
-    let ts = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
-    let temp_diff = std::env::temp_dir().join(format!("{}.{}.tmp", file_name, ts));
-
-    // --- 3. 差分生成 (hdiff) ---
-    if algo == "bsdiff" {
-        return Err(String::from("`bsdiff` is not supported yet."));
-    } else {
-        crate::app::hdiff::create_hdiff(
-            app.clone(),
-            &base_full.to_string_lossy(),
-            &work_file,
-            &temp_diff.to_string_lossy(),
-            &compress,
-        )
-        .await?;
-    }
-
-    // --- 4. サイズ・閾値判定 ---
-    let work_size = fs::metadata(&work_file).map_err(|e| e.to_string())?.len();
-    let diff_size = fs::metadata(&temp_diff).map_err(|e| e.to_string())?.len();
-
-    let threshold = {
-        let state = app.state::<AppState>();
-        let cfg = state.config.lock().unwrap();
-        let t = cfg.auto_base_generation_threshold;
-        if t <= 0.0 {
-            0.8
-        } else {
-            t
-        }
-    };
-
-    println!(
-        "DEBUG: work_size={}, diff_size={}, threshold={}, current_idx={}",
-        work_size, diff_size, threshold, current_idx
-    );
-
-    let mut should_next_gen = false;
-    if work_size > 100 * 1024 {
-        if (diff_size as f64) > (work_size as f64) * threshold {
-            should_next_gen = true;
-            println!("DEBUG: Threshold exceeded. Rotation triggered.");
-        }
-    }
-
-    if should_next_gen {
-        // --- 5a. 【世代交代】 ここを新しいロジックに差し替えます ---
-        let _ = fs::remove_file(&temp_diff);
-
-        // ★修正：既存の最新世代があるか再確認
-        let (new_gen_dir, actual_idx) = match auto_generation::get_latest_generation(&project_root)?
-        {
-            Some(info) if info.base_idx > current_idx => {
-                println!(
-                    "DEBUG: Existing newer generation found (idx {}). Using it.",
-                    info.base_idx
-                );
-                (info.dir_path, info.base_idx)
-            }
-            _ => {
-                let next_idx = current_idx + 1;
-                println!("DEBUG: Creating next generation: idx {}", next_idx);
-                let path =
-                    auto_generation::create_new_generation(&project_root, next_idx, &work_file)?;
-                (path, next_idx)
-            }
-        };
-
-        // 以降、決定した new_gen_dir を使って diff を作成
-        let new_base_full = new_gen_dir.join(format!("{}.base", file_name));
-        let final_path = new_gen_dir.join(format!("{}.{}.{}.diff", file_name, ts, algo));
-
-        // 念のため、既存フォルダを使う場合に .base が無いならコピーする（より安全にする場合）
-        if !new_base_full.exists() {
-            fs::copy(&work_file, &new_base_full).map_err(|e| e.to_string())?;
-        }
-
-        crate::app::hdiff::create_hdiff(
-            app.clone(),
-            &new_base_full.to_string_lossy(),
-            &work_file,
-            &final_path.to_string_lossy(),
-            &compress,
-        )
-        .await
-    } else {
-        // --- 5b. 【維持】 現在のフォルダ内に diff を確定 ---
-        let final_path = target_dir.join(format!("{}.{}.{}.diff", file_name, ts, algo));
-
-        if let Err(e) = fs::rename(&temp_diff, &final_path) {
-            if e.kind() == std::io::ErrorKind::CrossesDevices {
-                println!("DEBUG: Cross-device link detected. Falling back to copy & remove.");
-                fs::copy(&temp_diff, &final_path)
-                    .map_err(|e| format!("Failed to copy diff to destination: {}", e))?;
-                fs::remove_file(&temp_diff)
-                    .map_err(|e| format!("Failed to remove temp file: {}", e))?;
-            } else {
-                return Err(format!(
-                    "Failed to finalize diff (OS error {}): {}",
-                    e.raw_os_error().unwrap_or(0),
-                    e
-                ));
-            }
-        }
-
-        Ok(())
-    }
+    job_id: Option<String>,
+) -> Result<BackupOutcome, BackupError> {
+    let settings = app.state::<AppState>().backup_settings();
+    // 差分生成はCPU負荷が高いので非同期ランタイムをブロックしないよう別スレッドで実行
+    run_job(&app, job_id, "backup", work_file.clone(), move |job| {
+        backup::backup_or_diff(&work_file, &custom_dir, &algo, &compress, &settings, job)
+    })
+    .await
 }
 
 #[tauri::command]
@@ -308,30 +131,79 @@ pub async fn apply_multi_diff(
     app: AppHandle,
     work_file: String,
     diff_paths: Vec<String>,
-) -> Result<(), String> {
-    for dp in diff_paths {
-        let diff_name = Path::new(&dp).file_name().unwrap().to_string_lossy();
-
-        let result = if diff_name.contains(".bsdiff.") {
-            return Err(String::from("`bsdiff` is not supported."));
-        } else if diff_name.contains(".hdiff.") {
-            apply_hdiff_wrapper(app.clone(), work_file.as_str(), dp.as_str()).await
-        } else {
-            // 古いファイルのリトライ戦略
-            match apply_hdiff_wrapper(app.clone(), work_file.as_str(), dp.as_str()).await {
-                Ok(_) => Ok(()),
-                Err(e) => {
-                    // bsdiffリトライの枠だけ
-                    Err(format!("recovery failed for old format: {}", e))
-                }
-            }
-        };
+    options: Option<RestoreOptions>,
+    job_id: Option<String>,
+) -> Result<Vec<String>, BackupError> {
+    let state = app.state::<AppState>();
+    let settings = state.backup_settings();
+    let options = state.restore_options(options);
+    run_job(&app, job_id, "restore", work_file.clone(), move |job| {
+        restore::apply_multi_diff(&work_file, &diff_paths, &options, &settings, job)
+    })
+    .await
+}
 
-        if let Err(e) = result {
-            return Err(format!("復元失敗 ({}): {}", diff_name, e));
+/// 実行中のジョブを中断する (書きかけのファイルは各処理が削除する)
+/// 該当するジョブが無ければ false を返す
+#[tauri::command]
+pub fn cancel_job(state: State<'_, AppState>, job_id: String) -> bool {
+    match state.jobs.lock().unwrap().get(&job_id) {
+        Some(job) => {
+            job.cancel();
+            true
         }
+        None => false,
     }
-    Ok(())
+}
+
+/// ジョブキューの中身 (実行中 / 待機中)
+#[tauri::command]
+pub fn get_job_queue(state: State<'_, AppState>) -> Vec<QueuedJob> {
+    state.queue.snapshot()
+}
+
+// 進捗を "job-progress" で通知し、cancel_job で中断できるジョブとして別スレッドで実行する
+// 同じ作業ファイルのジョブはキューで 1 件ずつ順番に実行する
+async fn run_job<T, F>(
+    app: &AppHandle,
+    job_id: Option<String>,
+    kind: &'static str,
+    work_file: String,
+    f: F,
+) -> Result<T, BackupError>
+where
+    T: Send + 'static,
+    F: FnOnce(&JobContext) -> Result<T, BackupError> + Send + 'static,
+{
+    let handle = app.clone();
+    let event_id = job_id.clone();
+    let job = JobContext::new(move |progress| {
+        let _ = handle.emit(
+            "job-progress",
+            JobProgressEvent {
+                job_id: event_id.clone(),
+                progress: progress.clone(),
+            },
+        );
+    });
+
+    let state = app.state::<AppState>();
+    if let Some(id) = &job_id {
+        state.jobs.lock().unwrap().insert(id.clone(), job.clone());
+    }
+    let worker = job.clone();
+    let handle = app.clone();
+    let queue_id = job_id.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let state = handle.state::<AppState>();
+        let _ticket = state.queue.acquire(&work_file, queue_id, kind, &worker)?;
+        f(&worker)
+    })
+    .await;
+    if let Some(id) = &job_id {
+        state.jobs.lock().unwrap().remove(id);
+    }
+    result.map_err(BackupError::internal)?
 }
 
 #[tauri::command]
@@ -353,19 +225,19 @@ pub fn get_config_dir(app: AppHandle) -> String {
 }
 
 #[tauri::command]
-pub fn get_file_size(path: String) -> Result<i64, String> {
+pub fn get_file_size(path: String) -> Result<i64, BackupError> {
     if path.is_empty() {
-        return Err("path is empty".to_string());
+        return Err(BackupError::new(ErrorKind::InvalidInput, "path is empty"));
     }
 
     let p = Path::new(&path);
 
     // ファイルのメタデータを取得 (os.Stat 相当)
-    let metadata = fs::metadata(p).map_err(|e| e.to_string())?;
+    let metadata = fs::metadata(p).at(p)?;
 
     // ディレクトリの場合はエラーを返す
     if metadata.is_dir() {
-        return Err("path is a directory".to_string());
+        return Err(BackupError::new(ErrorKind::InvalidInput, "path is a directory").with_path(p));
     }
 
     // サイズを返す (i64にキャスト)
@@ -373,11 +245,11 @@ pub fn get_file_size(path: String) -> Result<i64, String> {
 }
 
 #[tauri::command]
-pub async fn select_any_file(app: AppHandle, title: String) -> Result<Option<String>, String> {
+pub async fn select_any_file(app: AppHandle, title: String) -> Result<Option<String>, BackupError> {
     // 1. メインウィンドウとAppStateを取得
     let window = app
         .get_webview_window("main")
-        .ok_or("Main window not found")?;
+        .ok_or_else(|| BackupError::internal("Main window not found"))?;
     let state = app.state::<AppState>();
 
     // 2. 現在の AlwaysOnTop 設定を確認し、有効なら一時解除
@@ -416,11 +288,11 @@ pub async fn select_any_file(app: AppHandle, title: String) -> Result<Option<Str
 
 /// フォルダ選択ダイアログを表示する
 #[tauri::command]
-pub async fn select_backup_folder(app: AppHandle) -> Result<Option<String>, String> {
+pub async fn select_backup_folder(app: AppHandle) -> Result<Option<String>, BackupError> {
     // 1. メインウィンドウとAppStateを取得
     let window = app
         .get_webview_window("main")
-        .ok_or("Main window not found")?;
+        .ok_or_else(|| BackupError::internal("Main window not found"))?;
     let state = app.state::<AppState>();
 
     // 2. 現在の AlwaysOnTop 設定を確認し、有効なら一時解除
@@ -467,7 +339,7 @@ pub async fn select_backup_folder(app: AppHandle) -> Result<Option<String>, Stri
 }
 
 #[tauri::command]
-pub fn open_directory(app: tauri::AppHandle, path: String) -> Result<(), String> {
+pub fn open_directory(app: tauri::AppHandle, path: String) -> Result<(), BackupError> {
     // 1. パスの親ディレクトリ（フォルダ）を取得
     let target = std::path::Path::new(&path)
         .parent()
@@ -477,24 +349,27 @@ pub fn open_directory(app: tauri::AppHandle, path: String) -> Result<(), String>
     // OS自体の別アプリ（Explorer/Finder）を起動するため app.shell() のままで問題ありません
     app.shell()
         .open(target.to_string_lossy().to_string(), None)
-        .map_err(|e| e.to_string())?;
+        .map_err(BackupError::internal)?;
 
     Ok(())
 }
 
 // コマンド用ラッパー
 #[tauri::command]
-pub async fn toggle_compact_mode(window: WebviewWindow, is_compact: bool) -> Result<(), String> {
-    utils::apply_compact_mode(&window, is_compact).map_err(|e| e.to_string())
+pub async fn toggle_compact_mode(
+    window: WebviewWindow,
+    is_compact: bool,
+) -> Result<(), BackupError> {
+    utils::apply_compact_mode(&window, is_compact).map_err(BackupError::internal)
 }
 
 #[tauri::command]
-pub async fn toggle_window_visibility(app: AppHandle, show: bool) -> Result<(), String> {
+pub async fn toggle_window_visibility(app: AppHandle, show: bool) -> Result<(), BackupError> {
     utils::apply_window_visibility(app, show)
 }
 
 #[tauri::command]
-pub fn read_text_file(path: String) -> Result<String, String> {
+pub fn read_text_file(path: String) -> Result<String, BackupError> {
     let p = std::path::Path::new(&path);
 
     // 1. ファイルが存在するかチェック
@@ -509,267 +384,347 @@ pub fn read_text_file(path: String) -> Result<String, String> {
         Ok(content) => Ok(content),
         Err(e) => {
             // 読み込みに失敗した場合（権限不足など）はエラーを返す
-            Err(format!("Failed to read file: {}", e))
+            Err(e).at(p).context("Failed to read file")
         }
     }
 }
 
 /// 指定されたパスに文字列を書き込む (Goの WriteTextFile 相当)
 #[tauri::command]
-pub fn write_text_file(path: String, content: String) -> Result<(), String> {
+pub fn write_text_file(path: String, content: String) -> Result<(), BackupError> {
     let path_obj = Path::new(&path);
 
     // 親ディレクトリが存在しない場合は作成する (Go版より少し親切な設計)
     if let Some(parent) = path_obj.parent() {
         if !parent.exists() {
-            fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+            fs::create_dir_all(parent)
+                .at(parent)
+                .context("Failed to create directory")?;
         }
     }
 
     // ファイル書き込み (0644相当はRustの標準的な挙動)
-    fs::write(path_obj, content).map_err(|e| format!("Failed to write text file: {}", e))?;
+    fs::write(path_obj, content)
+        .at(path_obj)
+        .context("Failed to write text file")?;
 
     Ok(())
 }
 
 #[tauri::command]
-pub fn get_backup_list(work_file: String, backup_dir: String) -> Result<Vec<BackupItem>, String> {
-    let mut list = Vec::new();
-
-    // --- 1. ルートディレクトリの決定 ---
-    let root = if backup_dir.is_empty() {
-        utils::default_backup_dir(&work_file)
-    } else {
-        PathBuf::from(&backup_dir)
-    };
-
-    if !root.exists() {
-        return Ok(list);
-    }
-
-    // ファイル名（拡張子なし）を取得
-    let file_path_obj = Path::new(&work_file);
-    let base_name_only = file_path_obj
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("");
-
-    let file_path_ext: String = match file_path_obj.extension().and_then(|s| s.to_str()) {
-        Some(ext) => format!(".{}", ext),
-        None => String::new(),
-    };
-    let mut valid_exts = vec![
-        ".diff".to_string(),
-        ".zip".to_string(),
-        ".tar.gz".to_string(),
-        ".tar".to_string(),
-        ".gz".to_string(),
-    ];
-    if !file_path_ext.is_empty() {
-        valid_exts.push(file_path_ext.to_lowercase());
-    }
-    // 拡張子判定ヘルパー
-    let is_valid_ext = |name: &str| -> bool {
-        let n = name.to_lowercase();
-        valid_exts
-            .iter()
-            .any(|ext| n.ends_with(&ext.to_lowercase()))
-    };
+pub fn get_backup_list(
+    work_file: String,
+    backup_dir: String,
+) -> Result<Vec<BackupItem>, BackupError> {
+    history::get_backup_list(&work_file, &backup_dir)
+}
 
-    // --- 1. ルート直下のアーカイブをスキャン ---
-    if let Ok(entries) = fs::read_dir(&root) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                continue;
-            }
-            let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-            let f_name_lower = file_name.to_lowercase();
-            let base_lower = base_name_only.to_lowercase();
-            if f_name_lower.contains(&base_lower) && is_valid_ext(file_name) {
-                // fs::metadata で確実に最新のサイズを取得
-                if let Ok(metadata) = fs::metadata(&path) {
-                    list.push(create_backup_item(file_name, &path, &metadata, 0));
-                }
-            }
-        }
-    }
+/// 共有のバックアップ先フォルダの履歴を、元の作業ファイル名ごとに分けて返す
+#[tauri::command]
+pub fn get_backups_by_work_file(
+    backup_dir: String,
+) -> Result<BTreeMap<String, Vec<BackupItem>>, BackupError> {
+    history::get_backups_by_work_file(&backup_dir)
+}
 
-    // --- 2. すべての世代フォルダ(base*)をスキャン ---
-    if let Ok(entries) = fs::read_dir(&root) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if !path.is_dir() {
-                continue;
-            }
+// 以下の履歴の変更は checksum.json / manifest.json / 索引を書き換えるため、
+// 同じ作業ファイルのバックアップと重ならないよう run_job のキューで順番を待つ
 
-            let dir_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-
-            if dir_name.starts_with("base") {
-                // 【修正】base1_2024... から "1" だけを取り出すロジック
-                let gen_idx: i32 = dir_name
-                    .strip_prefix("base")
-                    .and_then(|s| s.split('_').next()) // アンダーバーで区切って最初の要素を取る
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(0);
-
-                if let Ok(gen_entries) = fs::read_dir(&path) {
-                    for gen_entry in gen_entries.flatten() {
-                        let gen_path = gen_entry.path();
-                        let f_name = gen_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-
-                        // 除外条件
-                        if gen_path.is_dir()
-                            || f_name.ends_with(".base")
-                            || f_name == "checksum.json"
-                        {
-                            continue;
-                        }
-                        let f_name_lower = f_name.to_lowercase();
-                        let base_lower = base_name_only.to_lowercase();
-                        if f_name_lower.contains(&base_lower) && is_valid_ext(f_name) {
-                            // 世代フォルダ内のファイルも fs::metadata でサイズを確定
-                            if let Ok(metadata) = fs::metadata(&gen_path) {
-                                list.push(create_backup_item(
-                                    f_name, &gen_path, &metadata, gen_idx,
-                                ));
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
+/// バックアップをマイルストーンとしてピン留め / 解除する (label は "納品版" など)
+#[tauri::command]
+pub async fn set_backup_pinned(
+    app: AppHandle,
+    work_file: String,
+    path: String,
+    pinned: bool,
+    label: String,
+) -> Result<(), BackupError> {
+    run_job(&app, None, "pin", work_file, move |_| {
+        pin::set_pinned(&path, pinned, &label)
+    })
+    .await
+}
 
-    Ok(list)
+/// バックアップのメモを保存する (.note と、世代フォルダの manifest.json)
+#[tauri::command]
+pub async fn save_backup_note(
+    app: AppHandle,
+    work_file: String,
+    path: String,
+    note: String,
+) -> Result<(), BackupError> {
+    run_job(&app, None, "note", work_file, move |_| {
+        manifest::set_note(&path, &note)
+    })
+    .await
 }
 
-// ヘルパー関数: 拡張子チェック
-fn is_valid_backup_ext(name: &str, exts: &[&str]) -> bool {
-    exts.iter().any(|&ext| name.ends_with(ext))
+/// バックアップ 1 件を削除する (.note も削除)
+/// .base は、それを使う差分があれば cascade 指定時のみ差分ごと削除する
+#[tauri::command]
+pub async fn delete_backup(
+    app: AppHandle,
+    work_file: String,
+    path: String,
+    cascade: bool,
+) -> Result<Vec<String>, BackupError> {
+    run_job(&app, None, "delete", work_file, move |_| {
+        delete::delete_backup(&path, cascade)
+    })
+    .await
 }
 
-// ヘルパー関数: アイテム生成 (日付フォーマット含む)
-fn create_backup_item(name: &str, path: &Path, meta: &fs::Metadata, gen: i32) -> BackupItem {
-    let modified: DateTime<Local> = meta
-        .modified()
-        .unwrap_or_else(|_| std::time::SystemTime::now())
-        .into();
-    BackupItem {
-        file_name: name.to_string(),
-        file_path: path.to_string_lossy().into_owned(),
-        timestamp: modified.format("%Y-%m-%d %H:%M:%S").to_string(),
-        file_size: meta.len() as i64,
-        generation: gen,
-    }
+/// 世代フォルダ (baseN_...) から作業ファイルの .base と差分を削除する
+#[tauri::command]
+pub async fn delete_generation(
+    app: AppHandle,
+    work_file: String,
+    dir: String,
+) -> Result<Vec<String>, BackupError> {
+    run_job(&app, None, "delete", work_file.clone(), move |_| {
+        delete::delete_generation(&work_file, &dir)
+    })
+    .await
 }
 
 /// ファイルをそのままコピーしてバックアップする (Go版の CopyBackupFile 相当)
 #[tauri::command]
-pub fn copy_backup_file(src: String, backup_dir: String) -> Result<String, String> {
-    // 1. バックアップ先ディレクトリの決定
-    // backup_dir が空ならソースファイルに基づいたデフォルトディレクトリを作成
-    let target_dir = if backup_dir.is_empty() {
-        utils::default_backup_dir(&src)
-    } else {
-        PathBuf::from(backup_dir)
-    };
-
-    // 2. ディレクトリの作成 (MkdirAll 0755 相当)
-    // utils::copy_file 内部でも作成していますが、Go版の構造に合わせここで明示的に作成
-    if !target_dir.exists() {
-        fs::create_dir_all(&target_dir)
-            .map_err(|e| format!("バックアップ先フォルダの作成に失敗しました: {}", e))?;
-    }
-
-    // 3. タイムスタンプ付きファイル名の生成 (例: filename_20260111_120000.ext)
-    let new_filename = utils::timestamped_name(&src);
-
-    // 4. 保存先のフルパスを組み立て
-    let dest_path = target_dir.join(new_filename);
-    let dest_str = dest_path.to_string_lossy();
-
-    // 5. utils::copy_file (Sync処理付き) を実行
-    utils::copy_file(&src, &dest_str).map_err(|e| e.to_string())?;
-
-    // 6. 成功したら保存先のパスを返す (JS側での表示用)
-    Ok(dest_str.into_owned())
+pub async fn copy_backup_file(
+    app: AppHandle,
+    src: String,
+    backup_dir: String,
+    job_id: Option<String>,
+) -> Result<BackupOutcome, BackupError> {
+    let settings = app.state::<AppState>().backup_settings();
+    run_job(&app, job_id, "backup", src.clone(), move |job| {
+        backup::copy_backup_file(&src, &backup_dir, &settings, job)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn archive_backup_file(
+    app: AppHandle,
     src: String,
     backup_dir: String,
     format: String,
     password: String,
-) -> Result<String, String> {
-    // 1. バックアップ先の決定
-    let target_dir = if backup_dir.is_empty() {
-        utils::default_backup_dir(&src)
-    } else {
-        std::path::PathBuf::from(backup_dir)
-    };
-
-    if !target_dir.exists() {
-        fs::create_dir_all(&target_dir).map_err(|e| e.to_string())?;
-    }
-
-    // 2. フォーマットによる分岐
-    if format == "zip" {
-        utils::zip_backup_file(&src, &target_dir, &password).map_err(|e| e.to_string())?;
-    } else {
-        utils::tar_backup_file(&src, &target_dir).map_err(|e| e.to_string())?;
-    }
-
-    Ok("Archive created successfully".to_string())
+    job_id: Option<String>,
+) -> Result<String, BackupError> {
+    let settings = app.state::<AppState>().backup_settings();
+    run_job(&app, job_id, "backup", src.clone(), move |job| {
+        backup::archive_backup_file(&src, &backup_dir, &format, &password, &settings, job)
+    })
+    .await
 }
 
 /// 指定されたパスがディレクトリとして存在するか確認します (Go版の DirExists 相当)
 #[tauri::command]
-pub fn dir_exists(path: String) -> Result<bool, String> {
+pub fn dir_exists(path: String) -> Result<bool, BackupError> {
     let p = Path::new(&path);
     // exists() かつ is_dir() であることを1行で判定できます
     Ok(p.is_dir())
 }
 
+/// 履歴のバックアップ 1 件を復元し、復元したファイルのパスを返す
+/// options が無ければ作業ファイルの隣に別名で復元する
 #[tauri::command]
 pub async fn restore_backup(
     app: tauri::AppHandle,
     path: String,
     work_file: String,
-) -> Result<(), String> {
-    let lower_path = path.to_lowercase();
+    options: Option<RestoreOptions>,
+    job_id: Option<String>,
+) -> Result<String, BackupError> {
+    let state = app.state::<AppState>();
+    let settings = state.backup_settings();
+    let options = state.restore_options(options);
+    run_job(&app, job_id, "restore", work_file.clone(), move |job| {
+        restore::restore_backup(&path, &work_file, &options, &settings, job)
+    })
+    .await
+}
 
-    // 1. 差分パッチ (.diff)
-    if lower_path.ends_with(".diff") {
-        return apply_multi_diff(app, work_file, vec![path]).await;
-    }
+/// 作業ファイルを直接置き換えて復元する
+/// 置き換える前の作業ファイルは backup_dir にフルコピーとしてバックアップされる
+#[tauri::command]
+pub async fn restore_in_place(
+    app: tauri::AppHandle,
+    path: String,
+    work_file: String,
+    backup_dir: String,
+    job_id: Option<String>,
+) -> Result<InPlaceRestore, BackupError> {
+    let settings = app.state::<AppState>().backup_settings();
+    run_job(&app, job_id, "restore", work_file.clone(), move |job| {
+        restore::restore_in_place(&path, &work_file, &backup_dir, &settings, job)
+    })
+    .await
+}
+
+/// バックアップ履歴を検証し、壊れた差分や .base の無い差分を報告する
+#[tauri::command]
+pub async fn verify_backups(
+    app: AppHandle,
+    work_file: String,
+    backup_dir: String,
+) -> Result<Vec<VerifyResult>, BackupError> {
+    let settings = app.state::<AppState>().backup_settings();
+    tauri::async_runtime::spawn_blocking(move || {
+        verify::verify_backups(&work_file, &backup_dir, &settings)
+    })
+    .await
+    .map_err(BackupError::internal)?
+}
 
-    // 復元先のパスを「別名」として自動生成
-    let restored_path = utils::auto_output_path(&work_file);
+/// 保存先に残った書きかけの一時ファイル (前回の異常終了など) を消し、消したファイルを返す
+/// 実行中のジョブの一時ファイルは消さない (古いものだけが対象)
+#[tauri::command]
+pub async fn sweep_temporaries(
+    work_file: String,
+    backup_dir: String,
+) -> Result<Vec<String>, BackupError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = if backup_dir.is_empty() {
+            cg_backup_core::utils::default_backup_dir(&work_file)
+        } else {
+            PathBuf::from(&backup_dir)
+        };
+        let removed = atomic::sweep_stale(&root)?;
+        Ok(removed
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect())
+    })
+    .await
+    .map_err(BackupError::internal)?
+}
 
-    // 2. ZIPアーカイブ
-    if lower_path.ends_with(".zip") {
-        let file = File::open(&path).map_err(|e| e.to_string())?;
-        let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
-        if archive.len() > 0 {
-            let mut file_in_zip = archive.by_index(0).map_err(|e| e.to_string())?;
-            return utils::save_to_work_file(&mut file_in_zip, &restored_path);
-        }
-    }
+/// 保持ルール (AppConfig の retention) に従って古いバックアップを削除する
+/// dry_run なら削除対象を返すだけで何も消さない
+#[tauri::command]
+pub async fn prune_backups(
+    app: AppHandle,
+    work_file: String,
+    backup_dir: String,
+    dry_run: bool,
+) -> Result<PruneReport, BackupError> {
+    let settings = app.state::<AppState>().backup_settings();
+    run_job(&app, None, "prune", work_file.clone(), move |_| {
+        prune::prune_backups(&work_file, &backup_dir, &settings.retention, dry_run)
+    })
+    .await
+}
 
-    // 3. TARアーカイブ (.tar.gz)
-    if lower_path.ends_with(".tar.gz") {
-        let file = File::open(&path).map_err(|e| e.to_string())?;
-        let tar_gz = GzDecoder::new(file);
-        let mut archive = Archive::new(tar_gz);
-        if let Some(Ok(mut entry)) = archive.entries().map_err(|e| e.to_string())?.next() {
-            return utils::save_to_work_file(&mut entry, &restored_path);
-        }
+/// タブの作業ファイルの監視を開始する
+/// 保存が落ち着くたびに request の設定でバックアップし、結果を "auto-backup" で通知する
+#[tauri::command]
+pub fn start_watch(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    tab_id: u64,
+    request: BackupRequest,
+) -> Result<(), BackupError> {
+    fs::metadata(&request.work_file).at(&request.work_file)?;
+
+    let handle = app.clone();
+    let watcher = FileWatcher::spawn(
+        request.work_file.clone(),
+        WatchOptions::default(),
+        move |_| {
+            let state = handle.state::<AppState>();
+            let settings = state.backup_settings();
+            // 手動のバックアップなどと重ならないよう、キューで順番を待つ
+            let job = JobContext::default();
+            let result = state
+                .queue
+                .acquire(&request.work_file, None, "backup", &job)
+                .and_then(|_ticket| backup::run_backup(&request, &settings, &job));
+            if let Err(e) = &result {
+                log::warn!("Auto backup failed: {}", e);
+            }
+            let _ = handle.emit(
+                "auto-backup",
+                AutoBackupEvent::new(Some(tab_id), "watch", &request.work_file, &result),
+            );
+        },
+    );
+
+    // 同じタブの古い監視は置き換える
+    // 古い監視は実行中のバックアップの終了を待つことがあるので、ロックの外の別スレッドで止める
+    let old = state.watchers.lock().unwrap().insert(tab_id, watcher);
+    if let Some(old) = old {
+        tauri::async_runtime::spawn_blocking(move || drop(old));
     }
+    Ok(())
+}
+
+/// タブの作業ファイルの監視を止める
+#[tauri::command]
+pub async fn stop_watch(app: AppHandle, tab_id: u64) -> Result<(), BackupError> {
+    // 実行中のバックアップの終了を待つことがあるので別スレッドで止める
+    tauri::async_runtime::spawn_blocking(move || {
+        let old = app
+            .state::<AppState>()
+            .watchers
+            .lock()
+            .unwrap()
+            .remove(&tab_id);
+        drop(old);
+    })
+    .await
+    .map_err(BackupError::internal)
+}
+
+/// 監視中のタブ ID 一覧
+#[tauri::command]
+pub fn get_watching_tabs(state: State<'_, AppState>) -> Vec<u64> {
+    state.watchers.lock().unwrap().keys().copied().collect()
+}
 
-    // 4. フルコピー (.clip / .psd 等)
-    // 既存の utils::copy_file を使用
-    utils::copy_file(&path, &restored_path)?;
+/// 定期バックアップの登録一覧
+#[tauri::command]
+pub fn get_schedules(state: State<'_, AppState>) -> Vec<ScheduledBackup> {
+    state.config.lock().unwrap().schedules.clone()
+}
+
+/// 定期バックアップを登録 / 更新する (作業ファイル単位)
+#[tauri::command]
+pub fn set_schedule(
+    state: State<'_, AppState>,
+    schedule: ScheduledBackup,
+) -> Result<(), BackupError> {
+    if schedule.interval_minutes == 0 {
+        return Err(BackupError::new(
+            ErrorKind::InvalidInput,
+            "Interval must be at least 1 minute",
+        ));
+    }
+    update_schedules(&state, |list| {
+        list.retain(|s| s.request.work_file != schedule.request.work_file);
+        list.push(schedule);
+    })
+}
+
+/// 定期バックアップの登録を外す
+#[tauri::command]
+pub fn remove_schedule(state: State<'_, AppState>, work_file: String) -> Result<(), BackupError> {
+    update_schedules(&state, |list| {
+        list.retain(|s| s.request.work_file != work_file)
+    })
+}
+
+// 設定を更新して保存し、スケジューラに反映する
+fn update_schedules(
+    state: &AppState,
+    f: impl FnOnce(&mut Vec<ScheduledBackup>),
+) -> Result<(), BackupError> {
+    let schedules = {
+        let mut cfg = state.config.lock().unwrap();
+        f(&mut cfg.schedules);
+        cfg.schedules.clone()
+    };
+    state.save()?;
+    state.scheduler.set_jobs(schedules);
     Ok(())
 }
@@ -1,6 +1,6 @@
-use crate::app::types::AppConfig;
+use crate::app::types::{AppConfig, BackupError, ErrorKind};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::AppHandle;
 use tauri::Manager;
 
@@ -12,28 +12,37 @@ pub fn default_config() -> AppConfig {
         .expect("Embedded AppConfig.json is invalid. Please check the JSON format at compile time.")
 }
 
-pub fn load_app_config(app: &AppHandle) -> Result<(AppConfig, PathBuf), String> {
+pub fn load_app_config(app: &AppHandle) -> Result<(AppConfig, PathBuf), BackupError> {
     // ユーザーの設定ディレクトリを取得 (例: AppData/Roaming/cg-file-backup)
-    let app_dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    let app_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| BackupError::new(ErrorKind::Config, e.to_string()))?;
 
     // ディレクトリがなければ作成 (MkdirAll 相当)
     if !app_dir.exists() {
-        fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
+        fs::create_dir_all(&app_dir).map_err(|e| config_error(&app_dir, e))?;
     }
 
     let config_path = app_dir.join("AppConfig.json");
 
     let data = if config_path.exists() {
         // 既存ファイルを読み込む
-        fs::read_to_string(&config_path).map_err(|e| e.to_string())?
+        fs::read_to_string(&config_path).map_err(|e| config_error(&config_path, e))?
     } else {
         // なければデフォルトを書き込んで使う
-        fs::write(&config_path, DEFAULT_CONFIG_JSON).map_err(|e| e.to_string())?;
+        fs::write(&config_path, DEFAULT_CONFIG_JSON).map_err(|e| config_error(&config_path, e))?;
         DEFAULT_CONFIG_JSON.to_string()
     };
 
     // デシリアライズ
-    let cfg: AppConfig = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+    let cfg: AppConfig = serde_json::from_str(&data).map_err(|e| config_error(&config_path, e))?;
 
     Ok((cfg, config_path))
 }
+
+fn config_error(path: &Path, e: impl std::error::Error + Send + Sync + 'static) -> BackupError {
+    BackupError::new(ErrorKind::Config, "Failed to load config")
+        .with_path(path)
+        .with_source(e)
+}
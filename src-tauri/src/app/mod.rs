@@ -1,8 +1,5 @@
-pub mod auto_generation;
 pub mod commands;
 pub mod config;
-pub mod hdiff;
-pub mod hdiff_common;
 pub mod menu;
 pub mod state;
 pub mod tray;
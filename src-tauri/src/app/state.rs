@@ -1,4 +1,8 @@
-use crate::app::types::AppConfig;
+use crate::app::types::{AppConfig, BackupError, BackupSettings, ErrorKind, RestoreOptions};
+use cg_backup_core::progress::JobContext;
+use cg_backup_core::queue::JobQueue;
+use cg_backup_core::schedule::Scheduler;
+use cg_backup_core::watch::FileWatcher;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -8,13 +12,52 @@ use std::sync::Mutex;
 pub struct AppState {
     pub config: Mutex<AppConfig>,
     pub config_path: PathBuf,
+    // タブ ID ごとのファイル監視 (自動バックアップ)
+    pub watchers: Mutex<HashMap<u64, FileWatcher>>,
+    // 定期バックアップ (ウィンドウを隠していても動く)
+    pub scheduler: Scheduler,
+    // 実行中のジョブ (JS から渡されたジョブ ID ごと、cancel_job で中断する)
+    pub jobs: Mutex<HashMap<String, JobContext>>,
+    // 作業ファイルごとにバックアップ・復元を直列化するキュー
+    pub queue: JobQueue,
 }
 
 impl AppState {
-    pub fn save(&self) -> Result<(), String> {
+    pub fn save(&self) -> Result<(), BackupError> {
         let cfg = self.config.lock().unwrap();
-        let data = serde_json::to_string_pretty(&*cfg).map_err(|e| e.to_string())?;
-        fs::write(&self.config_path, data).map_err(|e: std::io::Error| e.to_string())?;
+        let data = serde_json::to_string_pretty(&*cfg).map_err(|e| config_error(self, e))?;
+        fs::write(&self.config_path, data).map_err(|e| config_error(self, e))?;
         Ok(())
     }
+
+    /// 現在の設定からバックアップエンジン用の設定を組み立てる
+    pub fn backup_settings(&self) -> BackupSettings {
+        let cfg = self.config.lock().unwrap();
+        BackupSettings {
+            auto_base_generation_threshold: cfg.auto_base_generation_threshold,
+            bsdiff_max_file_size: cfg.bsdiff_max_file_size,
+            hpatchz_path: cg_backup_core::utils::sidecar_path("hpatchz"),
+            retention: cfg.retention.clone(),
+            quota: cfg.quota.clone(),
+        }
+    }
+
+    /// JS から渡された復元先の指定に、設定のファイル名テンプレートを補う
+    pub fn restore_options(&self, options: Option<RestoreOptions>) -> RestoreOptions {
+        let mut options = options.unwrap_or_default();
+        if options.name_template.is_empty() {
+            options.name_template = self.config.lock().unwrap().restore_name_template.clone();
+        }
+        options
+    }
+}
+
+// 設定ファイルの読み書きエラーを Config 種別にまとめる
+fn config_error(
+    state: &AppState,
+    e: impl std::error::Error + Send + Sync + 'static,
+) -> BackupError {
+    BackupError::new(ErrorKind::Config, "Failed to save config")
+        .with_path(&state.config_path)
+        .with_source(e)
 }
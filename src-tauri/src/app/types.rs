@@ -1,7 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
 use std::sync::Mutex;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -12,45 +11,78 @@ pub struct AppConfig {
     pub restore_previous_state: bool,
     pub tray_mode: bool,
     pub auto_base_generation_threshold: f64,
+    // bsdiff はファイル全体をメモリに載せるため、対象サイズの上限を設ける
+    #[serde(default = "default_bsdiff_max_file_size")]
+    pub bsdiff_max_file_size: u64,
     pub i18n: HashMap<String, HashMap<String, String>>,
     #[serde(skip_serializing, default)]
     pub compact_mode: bool,
     pub tray_backup_mode: String,
+    // 定期バックアップの登録 (作業ファイルごと)
+    #[serde(default)]
+    pub schedules: Vec<ScheduledBackup>,
+    // 履歴の保持ルール (prune_backups で使う)
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    // 作業ファイルごとの保存容量の上限 (maxBytes が 0 なら無制限)
+    #[serde(default)]
+    pub quota: StorageQuota,
+    // 復元先のファイル名のテンプレート (空なら "{stem}_restored_{id}{ext}")
+    #[serde(default)]
+    pub restore_name_template: String,
 }
 
-// JS側で確実に受け取るための構造体
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct DiffFileInfo {
-    pub file_name: String, // test-project.clip.2025...diff
-    pub file_path: String, // フルパス
-    pub timestamp: String, // 2025... 部分
-    pub file_size: i64,
+fn default_bsdiff_max_file_size() -> u64 {
+    100_000_000
 }
 
-// 履歴リストに表示する各ファイルの情報を保持
-#[derive(Debug, Serialize, Deserialize, Clone)]
+// 自動バックアップ (ファイル監視など) の結果を JS に通知するイベント "auto-backup"
+#[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
-pub struct BackupItem {
-    pub file_name: String,
-    pub file_path: String,
-    pub timestamp: String,
-    pub file_size: i64,
-    pub generation: i32, // 世代番号
+pub struct AutoBackupEvent {
+    pub tab_id: Option<u64>,  // ファイル監視のときのタブ ID
+    pub source: &'static str, // "watch" / "schedule"
+    pub work_file: String,
+    pub outcome: Option<BackupOutcome>, // 作成したバックアップ / 変更なし (失敗時は None)
+    pub error: Option<serde_json::Value>, // 失敗時の BackupError
 }
 
-// 世代管理を司る構造体 (JSに送らない場合は Serialize 不要ですが、一応付与)
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct GenerationManager {
-    pub backup_root: String, // cg_backup_元ファイル名/ のパス
-    pub threshold: f64,      // ベース更新の閾値 (例: 0.8 = 80%)
+impl AutoBackupEvent {
+    pub fn new(
+        tab_id: Option<u64>,
+        source: &'static str,
+        work_file: &str,
+        result: &Result<BackupOutcome, BackupError>,
+    ) -> Self {
+        Self {
+            tab_id,
+            source,
+            work_file: work_file.to_string(),
+            outcome: result.as_ref().ok().cloned(),
+            error: result
+                .as_ref()
+                .err()
+                .and_then(|e| serde_json::to_value(e).ok()),
+        }
+    }
 }
 
-// 現在の世代情報
-#[derive(Debug, Serialize, Deserialize, Clone)]
+// 時間のかかる処理の進捗を JS に通知するイベント "job-progress"
+#[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
-pub struct BackupGenInfo {
-    pub dir_path: PathBuf,
-    pub base_idx: i32,
+pub struct JobProgressEvent {
+    pub job_id: Option<String>, // JS から渡されたジョブ ID (未指定なら None)
+    #[serde(flatten)]
+    pub progress: Progress, // phase / processed / total
 }
+
+// バックアップ関連の型はエンジン側 (cg-backup-core) で定義している
+pub use cg_backup_core::error::{BackupError, ErrorKind};
+pub use cg_backup_core::progress::{Phase, Progress};
+pub use cg_backup_core::queue::{QueueState, QueuedJob};
+pub use cg_backup_core::schedule::{QuietHours, ScheduledBackup};
+pub use cg_backup_core::types::{
+    BackupGenInfo, BackupItem, BackupOutcome, BackupRequest, BackupSettings, GenerationManager,
+    InPlaceRestore, PruneReport, RestoreOptions, RetentionPolicy, StorageQuota, VerifyResult,
+    VerifyStatus,
+};
@@ -6,6 +6,8 @@ use crate::app::types::AppConfig;
 use crate::app::utils;
 use app::menu::*;
 use app::tray::*;
+use cg_backup_core::queue::JobQueue;
+use std::collections::HashMap;
 use std::fs;
 use std::sync::Mutex;
 use tauri::AppHandle;
@@ -296,9 +298,20 @@ pub fn run() {
                 default_config()
             };
 
+            utils::sweep_temporaries_on_startup(&config.schedules);
+            let scheduler = utils::spawn_scheduler(app.handle(), config.schedules.clone());
+            // キューの中身が変わるたびに "job-queue" で JS に通知する
+            let handle = app.handle().clone();
+            let queue = JobQueue::new(move |jobs| {
+                let _ = handle.emit("job-queue", jobs.to_vec());
+            });
             app.manage(AppState {
                 config: Mutex::new(config.clone()),
                 config_path,
+                watchers: Mutex::new(HashMap::new()),
+                scheduler,
+                jobs: Mutex::new(HashMap::new()),
+                queue,
             });
 
             #[cfg(desktop)]
@@ -404,6 +417,7 @@ pub fn run() {
             set_always_on_top,
             get_restore_previous_state,
             get_auto_base_generation_threshold,
+            get_bsdiff_max_file_size,
             get_language_text,
             get_i18n,
             set_language,
@@ -414,6 +428,7 @@ pub fn run() {
             archive_backup_file,
             dir_exists,
             restore_backup,
+            restore_in_place,
             get_file_size,
             select_any_file,
             select_backup_folder,
@@ -422,6 +437,22 @@ pub fn run() {
             write_text_file,
             read_text_file,
             get_backup_list,
+            get_backups_by_work_file,
+            set_backup_pinned,
+            save_backup_note,
+            delete_backup,
+            delete_generation,
+            start_watch,
+            stop_watch,
+            get_watching_tabs,
+            get_schedules,
+            set_schedule,
+            remove_schedule,
+            cancel_job,
+            get_job_queue,
+            verify_backups,
+            prune_backups,
+            sweep_temporaries,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
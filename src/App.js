@@ -1,4 +1,4 @@
-import { GetI18N, GetFileSize, OnFileDrop } from "./tauri_exports";
+import { GetI18N, GetFileSize, OnFileDrop, SweepTemporaries } from "./tauri_exports";
 
 import {
   i18n,
@@ -21,7 +21,7 @@ import {
 } from "./ui";
 
 import { setupGlobalEvents } from "./events";
-import { switchTab } from "./actions";
+import { switchTab, refreshAutoBackup } from "./actions";
 // --- 初期化ロジック ---
 async function Initialize() {
   const data = await GetI18N();
@@ -33,7 +33,15 @@ async function Initialize() {
   setI18N(data);
 
   await restoreSession();
- 
+
+  // 前回の異常終了で残った書きかけの一時ファイルを掃除する (結果は待たない)
+  tabs
+    .filter((tab) => tab.workFile)
+    .forEach((tab) => {
+      SweepTemporaries(tab.workFile, tab.backupDir || "").catch((err) =>
+        console.warn("Sweep temporaries failed:", err)
+      );
+    });
 
   const setText = (id, text) => {
     const el = document.getElementById(id);
@@ -79,10 +87,26 @@ async function Initialize() {
 
   setText("execute-backup-btn", i18n.executeBtn);
 
+  setText("cancel-job-btn", i18n.cancelJobBtn);
+
+  setText("watch-mode-text", i18n.watchModeLabel);
+
+  setText("schedule-text", i18n.scheduleLabel);
+
+  setText("schedule-unit", i18n.scheduleUnit);
+
+  setText("schedule-quiet-text", i18n.scheduleQuietHours);
+
   setText("refresh-diff-btn", i18n.refreshBtn);
 
+  setText("delete-selected-btn", i18n.deleteSelectedBtn);
+
   setText("apply-selected-btn", i18n.applyBtn);
 
+  setText("restore-inplace-btn", i18n.restoreInPlaceBtn);
+
+  setText("restore-to-btn", i18n.restoreToBtn);
+
   setText("select-all-btn", i18n.selectAllBtn);
 
   setText("drop-modal-title", i18n.dropModalTitle);
@@ -229,6 +253,8 @@ function setupDragAndDrop() {
         UpdateAllUI();
 
         saveCurrentSession();
+
+        refreshAutoBackup(getActiveTab());
       }
     })();
   }, true);
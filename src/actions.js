@@ -5,6 +5,13 @@ import {
   RestoreBackup,
   GetFileSize,
   DirExists,
+  StartWatch,
+  StopWatch,
+  GetWatchingTabs,
+  GetSchedules,
+  SetSchedule,
+  RemoveSchedule,
+  CancelJob,
 } from "./tauri_exports";
 
 import {
@@ -13,6 +20,7 @@ import {
   getActiveTab,
   addToRecentFiles,
   saveCurrentSession,
+  formatError,
 } from "./state";
 
 import {
@@ -24,6 +32,37 @@ import {
   UpdateAllUI
 } from "./ui";
 
+// --- 実行中のジョブ (進捗表示と中断用) ---
+let currentJobId = null;
+let jobSeq = 0;
+
+// 新しいジョブ ID を発行する (Rust 側の "job-progress" イベントと照合する)
+export function beginJob() {
+  currentJobId = `job-${Date.now()}-${++jobSeq}`;
+  return currentJobId;
+}
+
+export function getCurrentJobId() {
+  return currentJobId;
+}
+
+export function endJob() {
+  currentJobId = null;
+}
+
+export async function cancelCurrentJob() {
+  if (currentJobId) await CancelJob(currentJobId).catch(console.error);
+}
+
+// 中断はエラー扱いにせず、メッセージだけ出す
+export function showJobError(err) {
+  if (err && err.kind === "cancelled") {
+    showFloatingMessage(i18n.jobCancelled);
+  } else {
+    alert(formatError(err));
+  }
+}
+
 // --- タブ操作ロジック ---
 export function switchTab(id) {
   tabs.forEach((t) => (t.active = t.id == id));
@@ -50,6 +89,7 @@ export function addTab() {
 }
 
 export function removeTab(id) {
+  StopWatch(id).catch(console.error);
   const index = tabs.findIndex((t) => t.id === id);
   const wasActive = tabs[index].active;
   tabs.splice(index, 1);
@@ -96,13 +136,17 @@ export async function OnExecute() {
   const archiveFormat = tab.archiveFormat || "zip";
 
   toggleProgress(true, i18n.processingMsg);
+  const jobId = beginJob();
   try {
     let successText = "";
 
     // --- A. 単純コピーモード ---
     if (mode === "copy") {
-      await CopyBackupFile(tab.workFile, tab.backupDir);
-      successText = i18n.copyBackupSuccess;
+      const result = await CopyBackupFile(tab.workFile, tab.backupDir, jobId);
+      successText =
+        result.status === "unchanged"
+          ? i18n.noChangesMsg
+          : i18n.copyBackupSuccess;
     }
     // --- B. アーカイブモード ---
     else if (mode === "archive") {
@@ -112,7 +156,7 @@ export async function OnExecute() {
           ? document.getElementById("archive-password").value
           : "";
       if (fmt === "zip-pass") fmt = "zip";
-      await ArchiveBackupFile(tab.workFile, tab.backupDir, fmt, pwd);
+      await ArchiveBackupFile(tab.workFile, tab.backupDir, fmt, pwd, jobId);
       successText = i18n.archiveBackupSuccess.replace(
         "{format}",
         fmt.toUpperCase(),
@@ -138,22 +182,139 @@ export async function OnExecute() {
 
       // Rust側(またはGo側)の関数を呼び出し
       // 引数に新しく compress を追加。algoがbsdiffの場合は内部で無視される設計
-      await BackupOrDiff(tab.workFile, targetPath, algo, compress);
+      const result = await BackupOrDiff(
+        tab.workFile,
+        targetPath,
+        algo,
+        compress,
+        jobId,
+      );
 
-      successText = `${i18n.diffBackupSuccess} (${algo.toUpperCase()}${algo === "hdiff" ? ":" + compress : ""})`;
+      // 前回の差分から変更が無ければ、新しい差分は作られない
+      successText =
+        result.status === "unchanged"
+          ? i18n.noChangesMsg
+          : `${i18n.diffBackupSuccess} (${algo.toUpperCase()}${algo === "hdiff" ? ":" + compress : ""})`;
     }
 
+    endJob();
     toggleProgress(false);
     showFloatingMessage(successText);
     UpdateAllUI();
     return successText;
   } catch (err) {
+    endJob();
     toggleProgress(false);
-    alert(err);
+    showJobError(err);
     return null;
   }
 }
 
+// --- 自動バックアップ (ファイル監視) ---
+// タブの設定から Rust 側に渡すバックアップ指定を組み立てる (OnExecute と同じ解釈)
+export function buildBackupRequest(tab) {
+  const mode = tab.backupMode || "diff";
+  let format = tab.archiveFormat || "zip";
+  const password =
+    format === "zip-pass"
+      ? document.getElementById("archive-password")?.value || ""
+      : "";
+  if (format === "zip-pass") format = "zip";
+  return {
+    workFile: tab.workFile,
+    backupDir:
+      mode === "diff"
+        ? tab.selectedTargetDir || tab.backupDir || ""
+        : tab.backupDir || "",
+    mode,
+    algo: tab.diffAlgo || "hdiff",
+    compress: tab.compressMode || "zstd",
+    format,
+    password,
+  };
+}
+
+export async function isWatching(tab) {
+  const ids = await GetWatchingTabs();
+  return ids.includes(tab.id);
+}
+
+// 監視の ON/OFF を切り替える (状態は Rust 側が持つ)
+export async function setWatchMode(tab, enabled) {
+  try {
+    if (enabled) {
+      if (!tab.workFile) {
+        alert(i18n.selectFileFirst);
+        return false;
+      }
+      await StartWatch(tab.id, buildBackupRequest(tab));
+      showFloatingMessage(i18n.watchStarted);
+    } else {
+      await StopWatch(tab.id);
+      showFloatingMessage(i18n.watchStopped);
+    }
+    return enabled;
+  } catch (err) {
+    alert(formatError(err));
+    return false;
+  }
+}
+
+// 作業ファイルに登録された定期バックアップ (無ければ undefined)
+export async function getSchedule(tab) {
+  if (!tab || !tab.workFile) return undefined;
+  const list = await GetSchedules();
+  return list.find((s) => s.workFile === tab.workFile);
+}
+
+// 画面の入力から定期バックアップの登録内容を作る
+function buildSchedule(tab) {
+  const interval = parseInt(
+    document.getElementById("schedule-interval")?.value,
+    10,
+  );
+  const start = document.getElementById("schedule-quiet-start")?.value || "";
+  const end = document.getElementById("schedule-quiet-end")?.value || "";
+  return {
+    ...buildBackupRequest(tab),
+    intervalMinutes: interval > 0 ? interval : 30,
+    quietHours: start && end ? { start, end } : null,
+    enabled: true,
+  };
+}
+
+// 定期バックアップの ON/OFF を切り替える (登録は作業ファイル単位で設定に保存される)
+export async function setScheduleMode(tab, enabled, silent = false) {
+  try {
+    if (enabled) {
+      if (!tab.workFile) {
+        alert(i18n.selectFileFirst);
+        return false;
+      }
+      await SetSchedule(buildSchedule(tab));
+      if (!silent) showFloatingMessage(i18n.scheduleStarted);
+    } else {
+      await RemoveSchedule(tab.workFile);
+      if (!silent) showFloatingMessage(i18n.scheduleStopped);
+    }
+    return enabled;
+  } catch (err) {
+    alert(formatError(err));
+    return false;
+  }
+}
+
+// タブの設定が変わったら、監視・定期バックアップを新しい設定で登録し直す
+export async function refreshAutoBackup(tab) {
+  if (!tab) return;
+  if (await isWatching(tab)) {
+    await StartWatch(tab.id, buildBackupRequest(tab)).catch(console.error);
+  }
+  if (await getSchedule(tab).catch(() => undefined)) {
+    await SetSchedule(buildSchedule(tab)).catch(console.error);
+  }
+}
+
 // --- 復元・適用ロジック ---
 export async function applySelectedBackups() {
   const tab = getActiveTab();
@@ -162,16 +323,19 @@ export async function applySelectedBackups() {
   ).map((el) => el.value);
   if (targets.length > 0 && confirm(i18n.restoreConfirm)) {
     toggleProgress(true, "Restoring...");
+    const jobId = beginJob();
     try {
       for (const p of targets) {
-        await RestoreBackup(p, tab.workFile);
+        await RestoreBackup(p, tab.workFile, jobId);
       }
+      endJob();
       toggleProgress(false);
       showFloatingMessage(i18n.diffApplySuccess);
       UpdateAllUI();
     } catch (err) {
+      endJob();
       toggleProgress(false);
-      alert(err);
+      showJobError(err);
     }
   }
 }
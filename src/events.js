@@ -2,9 +2,12 @@ import {
   SelectAnyFile,
   SelectBackupFolder,
   GetFileSize,
-  WriteTextFile,
   ReadTextFile,
   RestoreBackup,
+  RestoreInPlace,
+  SetBackupPinned,
+  SaveBackupNote,
+  DeleteBackup,
   EventsOn,
 } from "./tauri_exports";
 
@@ -14,6 +17,7 @@ import {
   addToRecentFiles,
   saveCurrentSession,
   recentFiles,
+  formatError,
 } from "./state";
 
 import {
@@ -21,11 +25,26 @@ import {
   UpdateDisplay,
   UpdateHistory,
   toggleProgress,
+  updateProgress,
+  renderJobQueue,
   showFloatingMessage,
+  showFloatingError,
   renderRecentFiles,
 } from "./ui";
 
-import { addTab, OnExecute,switchTab } from "./actions";
+import {
+  addTab,
+  OnExecute,
+  switchTab,
+  setWatchMode,
+  setScheduleMode,
+  refreshAutoBackup,
+  beginJob,
+  endJob,
+  getCurrentJobId,
+  cancelCurrentJob,
+  showJobError,
+} from "./actions";
 import { ask } from "@tauri-apps/plugin-dialog";
 import {
   isPermissionGranted,
@@ -63,6 +82,7 @@ export function setupGlobalEvents() {
       UpdateDisplay();
       UpdateHistory();
       saveCurrentSession();
+      refreshAutoBackup(tab);
       showFloatingMessage(i18n.updatedWorkFile);
     }
   };
@@ -76,6 +96,7 @@ export function setupGlobalEvents() {
       UpdateDisplay();
       UpdateHistory();
       saveCurrentSession();
+      refreshAutoBackup(tab);
       showFloatingMessage(i18n.updatedBackupDir);
     }
   };
@@ -100,6 +121,7 @@ export function setupGlobalEvents() {
       e.stopPropagation();
       tab.selectedTargetDir = genBadge.getAttribute("data-dir");
       saveCurrentSession();
+      refreshAutoBackup(tab);
       UpdateHistory();
       return;
     }
@@ -116,7 +138,8 @@ export function setupGlobalEvents() {
       const currentNote = await ReadTextFile(notePath).catch(() => "");
       showMemoDialog(currentNote, async (newText) => {
         try {
-          await WriteTextFile(notePath, newText);
+          // manifest.json にも記録するため、バックエンド経由で保存する
+          await SaveBackupNote(tab.workFile, path, newText);
           showFloatingMessage(i18n.memoSaved);
           UpdateHistory();
         } catch (err) {
@@ -126,6 +149,27 @@ export function setupGlobalEvents() {
       });
       return;
     }
+    // 3. 履歴のピン留めボタン (.diff-item 内の .pin-btn) のクリック
+    const historyPinBtn = e.target.closest(".diff-item .pin-btn");
+    if (historyPinBtn) {
+      e.preventDefault();
+      e.stopPropagation();
+      const path = historyPinBtn.getAttribute("data-path");
+      const pinned = historyPinBtn.getAttribute("data-pinned") === "true";
+
+      try {
+        // ラベルにはメモの 1 行目を使う ("納品版" など)
+        const note = await ReadTextFile(path + ".note").catch(() => "");
+        const label = note.split("\n")[0].trim();
+        await SetBackupPinned(tab.workFile, path, !pinned, label);
+        showFloatingMessage(pinned ? i18n.unpinnedMsg : i18n.pinnedMsg);
+        UpdateHistory();
+      } catch (err) {
+        console.error(err);
+        alert(formatError(err));
+      }
+      return;
+    }
     // 最近使ったファイル (.recent-item) のクリック
     const recentItem = e.target.closest(".recent-item");
     if (recentItem) {
@@ -141,6 +185,7 @@ export function setupGlobalEvents() {
         // 状態更新と保存
         addToRecentFiles(path);
         saveCurrentSession();
+        refreshAutoBackup(tab);
 
         // UIの再描画
         renderRecentFiles();
@@ -169,6 +214,9 @@ export function setupGlobalEvents() {
     } else if (id === "execute-backup-btn" || id === "compact-execute-btn") {
       OnExecute();
       return;
+    } else if (id === "cancel-job-btn") {
+      cancelCurrentJob();
+      return;
     } else if (id === "refresh-diff-btn") {
       UpdateHistory();
       return;
@@ -177,6 +225,31 @@ export function setupGlobalEvents() {
       const all = Array.from(cbs).every((cb) => cb.checked);
       cbs.forEach((cb) => (cb.checked = !all));
       return;
+    } else if (id === "delete-selected-btn") {
+      e.preventDefault();
+      e.stopPropagation();
+      const targets = Array.from(
+        document.querySelectorAll(".diff-checkbox:checked"),
+      ).map((el) => el.value);
+      if (targets.length === 0) return;
+
+      const isConfirmed = await ask(i18n.deleteConfirm, {
+        title: "CG File Backup",
+        type: "warning",
+      });
+
+      if (isConfirmed) {
+        try {
+          for (const p of targets) {
+            await DeleteBackup(tab.workFile, p, false);
+          }
+          showFloatingMessage(i18n.deleteSuccess);
+        } catch (err) {
+          alert(formatError(err));
+        }
+        UpdateHistory();
+      }
+      return;
     } else if (id === "apply-selected-btn") {
       e.preventDefault();
       e.stopPropagation();
@@ -192,16 +265,80 @@ export function setupGlobalEvents() {
 
       if (isConfirmed) {
         toggleProgress(true, "Restoring...");
+        const jobId = beginJob();
         try {
           for (const p of targets) {
-            await RestoreBackup(p, tab.workFile);
+            await RestoreBackup(p, tab.workFile, jobId);
           }
+          endJob();
           toggleProgress(false);
           showFloatingMessage(i18n.diffApplySuccess);
           UpdateHistory();
         } catch (err) {
+          endJob();
+          toggleProgress(false);
+          showJobError(err);
+        }
+      }
+      return;
+    } else if (id === "restore-to-btn") {
+      e.preventDefault();
+      e.stopPropagation();
+      const targets = Array.from(
+        document.querySelectorAll(".diff-checkbox:checked"),
+      ).map((el) => el.value);
+      if (targets.length === 0) return;
+
+      // 復元先のフォルダを選ぶ (ファイル名は設定の restoreNameTemplate に従う)
+      const outputDir = await SelectBackupFolder();
+      if (!outputDir) return;
+
+      toggleProgress(true, "Restoring...");
+      const jobId = beginJob();
+      try {
+        for (const p of targets) {
+          await RestoreBackup(p, tab.workFile, jobId, { outputDir });
+        }
+        endJob();
+        toggleProgress(false);
+        showFloatingMessage(i18n.diffApplySuccess);
+        UpdateHistory();
+      } catch (err) {
+        endJob();
+        toggleProgress(false);
+        showJobError(err);
+      }
+      return;
+    } else if (id === "restore-inplace-btn") {
+      e.preventDefault();
+      e.stopPropagation();
+      const targets = Array.from(
+        document.querySelectorAll(".diff-checkbox:checked"),
+      ).map((el) => el.value);
+      if (targets.length !== 1) {
+        showFloatingError(i18n.restoreInPlaceSelectOne);
+        return;
+      }
+
+      const isConfirmed = await ask(i18n.restoreInPlaceConfirm, {
+        title: "CG File Backup",
+        type: "warning",
+      });
+
+      if (isConfirmed) {
+        toggleProgress(true, "Restoring...");
+        const jobId = beginJob();
+        try {
+          // 置き換える前の作業ファイルは履歴にフルコピーとして残る
+          await RestoreInPlace(targets[0], tab.workFile, tab.backupDir || "", jobId);
+          endJob();
+          toggleProgress(false);
+          showFloatingMessage(i18n.restoreInPlaceSuccess);
+          UpdateHistory();
+        } catch (err) {
+          endJob();
           toggleProgress(false);
-          alert(err);
+          showJobError(err);
         }
       }
       return;
@@ -218,6 +355,27 @@ export function setupGlobalEvents() {
       switchTab(Number(value));
       return;
     }
+    if (id === "watch-mode-toggle") {
+      setWatchMode(tab, e.target.checked).then((on) => {
+        e.target.checked = on;
+      });
+      return;
+    }
+    if (id === "schedule-toggle") {
+      setScheduleMode(tab, e.target.checked).then((on) => {
+        e.target.checked = on;
+      });
+      return;
+    }
+    if (
+      ["schedule-interval", "schedule-quiet-start", "schedule-quiet-end"].includes(id)
+    ) {
+      // 登録中なら新しい間隔・時間帯で登録し直す
+      if (document.getElementById("schedule-toggle")?.checked) {
+        setScheduleMode(tab, true, true);
+      }
+      return;
+    }
     if (name == "diff-algo") {
       if (tab) tab.diffAlgo = value;
     }
@@ -239,6 +397,7 @@ export function setupGlobalEvents() {
     ) {
       UpdateDisplay();
       saveCurrentSession();
+      refreshAutoBackup(tab);
     }
 
     if (id === "compact-mode-select") {
@@ -294,6 +453,37 @@ export function setupGlobalEvents() {
     }
   });
 
+  // 実行中のジョブの進捗 (他のジョブのイベントは無視する)
+  EventsOn("job-progress", (event) => {
+    if (event.jobId && event.jobId === getCurrentJobId()) updateProgress(event);
+  });
+
+  // バックアップ・復元のキュー (同じ作業ファイルのジョブは順番待ちになる)
+  EventsOn("job-queue", (jobs) => {
+    renderJobQueue(jobs, getCurrentJobId());
+  });
+
+  // ファイル監視による自動バックアップの結果
+  EventsOn("auto-backup", (event) => {
+    const tab = getActiveTab();
+    // 監視はタブ単位、定期バックアップは作業ファイル単位
+    const isOwnEvent =
+      tab &&
+      (event.source === "schedule"
+        ? tab.workFile === event.workFile
+        : tab.id === event.tabId);
+    if (event.error) {
+      showFloatingError(`${i18n.autoBackupFailed}: ${formatError(event.error)}`);
+    } else if (isOwnEvent) {
+      showFloatingMessage(
+        event.outcome?.status === "unchanged"
+          ? i18n.noChangesMsg
+          : i18n.autoBackupDone,
+      );
+    }
+    if (isOwnEvent) UpdateHistory();
+  });
+
   EventsOn("tray-change-work-clicked", () => {
     handleSelectWorkFile();
   });
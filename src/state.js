@@ -92,3 +92,14 @@ export function formatSize(bytes) {
   const i = Math.floor(Math.log(bytes) / Math.log(k));
   return parseFloat((bytes / Math.pow(k, i)).toFixed(2)) + " " + sizes[i];
 }
+
+// Rust 側のエラー ({kind, path, message, source, i18nKey}) を表示用の文字列にする
+export function formatError(err) {
+  if (!err || typeof err !== "object") return String(err);
+  const title = (i18n && i18n[err.i18nKey]) || err.message;
+  const lines = [title];
+  if (err.path) lines.push(err.path);
+  const detail = err.source ? `${err.message}: ${err.source}` : err.message;
+  if (detail && detail !== title) lines.push(detail);
+  return lines.join("\n");
+}
@@ -83,29 +83,36 @@ export async function DirExists(path) {
   return await invoke("dir_exists", { path });
 }
 
-export async function CopyBackupFile(src, backupDir) {
-  return await invoke("copy_backup_file", { src, backupDir });
+export async function CopyBackupFile(src, backupDir, jobId) {
+  return await invoke("copy_backup_file", { src, backupDir, jobId });
 }
 
-export async function RestoreBackup(path, workFile) {
-  return await invoke("restore_backup", { path, workFile });
+// options: { outputPath, outputDir, nameTemplate } (省略時は作業ファイルの隣に復元)
+export async function RestoreBackup(path, workFile, jobId, options = null) {
+  return await invoke("restore_backup", { path, workFile, options, jobId });
 }
 
-export async function ArchiveBackupFile(src, backupDir, format, password) {
+export async function RestoreInPlace(path, workFile, backupDir, jobId) {
+  return await invoke("restore_in_place", { path, workFile, backupDir, jobId });
+}
+
+export async function ArchiveBackupFile(src, backupDir, format, password, jobId) {
   return await invoke("archive_backup_file", {
     src,
     backupDir,
     format,
     password,
+    jobId,
   });
 }
 
-export async function BackupOrDiff(workFile, customDir, algo, compress) {
+export async function BackupOrDiff(workFile, customDir, algo, compress, jobId) {
   return await invoke("backup_or_diff", {
     workFile,
     customDir,
     algo,
     compress,
+    jobId,
   });
 }
 
@@ -113,6 +120,70 @@ export async function GetBackupList(workFile, backupDir) {
   return await invoke("get_backup_list", { workFile, backupDir });
 }
 
-export async function ApplyMultiDiff(workFile, diffPaths) {
-  return await invoke("apply_multi_diff", { workFile, diffPaths });
+export async function GetBackupsByWorkFile(backupDir) {
+  return await invoke("get_backups_by_work_file", { backupDir });
+}
+
+export async function SetBackupPinned(workFile, path, pinned, label) {
+  return await invoke("set_backup_pinned", { workFile, path, pinned, label });
+}
+
+export async function SaveBackupNote(workFile, path, note) {
+  return await invoke("save_backup_note", { workFile, path, note });
+}
+
+export async function DeleteBackup(workFile, path, cascade) {
+  return await invoke("delete_backup", { workFile, path, cascade });
+}
+
+export async function DeleteGeneration(workFile, dir) {
+  return await invoke("delete_generation", { workFile, dir });
+}
+
+export async function StartWatch(tabId, request) {
+  return await invoke("start_watch", { tabId, request });
+}
+
+export async function StopWatch(tabId) {
+  return await invoke("stop_watch", { tabId });
+}
+
+export async function GetWatchingTabs() {
+  return await invoke("get_watching_tabs");
+}
+
+export async function GetSchedules() {
+  return await invoke("get_schedules");
+}
+
+export async function SetSchedule(schedule) {
+  return await invoke("set_schedule", { schedule });
+}
+
+export async function RemoveSchedule(workFile) {
+  return await invoke("remove_schedule", { workFile });
+}
+
+export async function ApplyMultiDiff(workFile, diffPaths, jobId, options = null) {
+  return await invoke("apply_multi_diff", { workFile, diffPaths, options, jobId });
+}
+
+export async function CancelJob(jobId) {
+  return await invoke("cancel_job", { jobId });
+}
+
+export async function GetJobQueue() {
+  return await invoke("get_job_queue");
+}
+
+export async function VerifyBackups(workFile, backupDir) {
+  return await invoke("verify_backups", { workFile, backupDir });
+}
+
+export async function SweepTemporaries(workFile, backupDir) {
+  return await invoke("sweep_temporaries", { workFile, backupDir });
+}
+
+export async function PruneBackups(workFile, backupDir, dryRun) {
+  return await invoke("prune_backups", { workFile, backupDir, dryRun });
 }
@@ -2,7 +2,6 @@ import {
   GetBackupList,
   GetFileSize,
   WriteTextFile,
-  ReadTextFile,
   GetConfigDir,
 } from "./tauri_exports";
 
@@ -18,7 +17,13 @@ import {
 
 import { showMemoDialog } from "./memo.js";
 
-import { switchTab, removeTab, reorderTabs } from "./actions";
+import {
+  switchTab,
+  removeTab,
+  reorderTabs,
+  isWatching,
+  getSchedule,
+} from "./actions";
 
 // UI描画・メッセージ系（通常版）
 export function showFloatingMessage(text) {
@@ -263,6 +268,26 @@ export function UpdateDisplay() {
   if (dirEl) dirEl.textContent = tab.backupDir || i18n.selectedBackupDir;
 
 
+  // 自動バックアップの ON/OFF は Rust 側の状態に合わせる
+  const watchToggle = document.getElementById("watch-mode-toggle");
+  if (watchToggle) {
+    isWatching(tab).then((on) => (watchToggle.checked = on)).catch(() => {});
+  }
+  const scheduleToggle = document.getElementById("schedule-toggle");
+  if (scheduleToggle) {
+    getSchedule(tab)
+      .then((sch) => {
+        scheduleToggle.checked = !!sch;
+        if (!sch) return;
+        document.getElementById("schedule-interval").value = sch.intervalMinutes;
+        document.getElementById("schedule-quiet-start").value =
+          sch.quietHours?.start || "";
+        document.getElementById("schedule-quiet-end").value =
+          sch.quietHours?.end || "";
+      })
+      .catch(() => {});
+  }
+
   const radio = document.querySelector(`input[name="backupMode"][value="${tab.backupMode}"]`);
   if (radio) radio.checked = true;
   const compactModeSel = document.getElementById("compact-mode-select");
@@ -335,8 +360,8 @@ export async function UpdateHistory() {
       return;
     }
 
-    // --- ファイル名の降順でソート ---
-    data.sort((a, b) => b.fileName.localeCompare(a.fileName));
+    // --- 新しい順に表示 (一覧は作成日時の古い順で返ってくる) ---
+    data.reverse();
 
     // 1. 本来の最新世代を取得
     const latestGenNumber = Math.max(
@@ -362,9 +387,8 @@ export async function UpdateHistory() {
 
     const itemsHtml = await Promise.all(
       data.map(async (item) => {
-        const note = await ReadTextFile(item.filePath + ".note").catch(
-          () => "",
-        );
+        // メモは一覧 (バックアップ先の索引) に含まれている
+        const note = item.note || "";
 
         // --- 検索フィルタリング (ファイル名 または メモ に含まれるか) ---
         if (searchTerm) {
@@ -422,11 +446,13 @@ export async function UpdateHistory() {
               <div style="display:flex; flex-direction:column; flex:1; min-width:0;">
                 <span class="diff-name" data-hover-content="${encodeURIComponent(popupContent)}" style="font-weight:bold; overflow:hidden; text-overflow:ellipsis; white-space:nowrap;">
                   ${displayedFileName} ${genBadge} <span style="font-size:10px; color:#3B5998;">(${formatSize(item.fileSize)})</span>
+                  ${item.pinned ? `<span style="font-size:10px; color:#fff; background:#d9822b; padding:1px 4px; border-radius:3px; margin-left:5px;">${item.pinLabel || i18n.pinned || "Pinned"}</span>` : ""}
                 </span>
                 <span style="font-size:10px; color:#888;">${item.timestamp}</span>
                 ${note ? `<div style="font-size:10px; color:#2f8f5b; font-style:italic; overflow:hidden; text-overflow:ellipsis; white-space:nowrap;"> ${displayedNote}</div>` : ""}
               </div>
             </label>
+            <button class="pin-btn" data-path="${item.filePath}" data-pinned="${item.pinned}" title="${item.pinned ? i18n.unpinBackup || "Unpin" : i18n.pinBackup || "Pin as milestone"}" style="background:none; border:none; cursor:pointer; font-size:14px; padding:4px; opacity:${item.pinned ? 1 : 0.3};">📌</button>
             <button class="note-btn" data-path="${item.filePath}" style="background:none; border:none; cursor:pointer; font-size:14px; padding:4px;"></button>
           </div>
         </div>`;
@@ -501,8 +527,13 @@ export function toggleProgress(show, text = "") {
   const cBar = document.getElementById("compact-progress-bar");
   const cSts = document.getElementById("compact-status-label");
   const cBtn = document.getElementById("compact-execute-btn");
+  const cancelBtn = document.getElementById("cancel-job-btn");
 
   if (show) {
+    if (cancelBtn) {
+      cancelBtn.style.display = "inline-block";
+      cancelBtn.disabled = false;
+    }
     if (container) container.style.display = "block";
     if (status) {
       status.style.display = "block";
@@ -514,6 +545,7 @@ export function toggleProgress(show, text = "") {
     if (cBar) cBar.style.width = "0%";
     if (cBtn) cBtn.disabled = true;
   } else {
+    if (cancelBtn) cancelBtn.style.display = "none";
     if (bar) bar.style.width = "100%";
     if (cBar) cBar.style.width = "100%";
     setTimeout(() => {
@@ -527,6 +559,46 @@ export function toggleProgress(show, text = "") {
   }
 }
 
+// Rust 側の "job-progress" イベント ({phase, processed, total}) を表示に反映する
+export function updateProgress(event) {
+  const percent =
+    event.total > 0
+      ? Math.min(100, Math.floor((event.processed / event.total) * 100))
+      : 0;
+  const phaseKey = "phase" + event.phase.charAt(0).toUpperCase() + event.phase.slice(1);
+  const text = `${(i18n && i18n[phaseKey]) || event.phase} ${percent}%`;
+
+  const bar = document.getElementById("progress-bar");
+  const status = document.getElementById("progress-status");
+  const cBar = document.getElementById("compact-progress-bar");
+  const cSts = document.getElementById("compact-status-label");
+  if (bar) bar.style.width = `${percent}%`;
+  if (status) status.textContent = text;
+  if (cBar) cBar.style.width = `${percent}%`;
+  if (cSts) cSts.textContent = text;
+}
+
+// ジョブキューの状況を表示する (自分のジョブが順番待ちならその旨を出す)
+export function renderJobQueue(jobs, currentJobId) {
+  const el = document.getElementById("job-queue-status");
+  if (el) {
+    const running = jobs.filter((j) => j.state === "running").length;
+    const queued = jobs.length - running;
+    el.style.display = jobs.length > 0 ? "block" : "none";
+    el.textContent = (i18n.queueStatus || "Running: {running} / Waiting: {queued}")
+      .replace("{running}", running)
+      .replace("{queued}", queued);
+  }
+
+  const own = jobs.find((j) => j.jobId === currentJobId);
+  if (own && own.state === "queued") {
+    const status = document.getElementById("progress-status");
+    const cSts = document.getElementById("compact-status-label");
+    if (status) status.textContent = i18n.jobQueued;
+    if (cSts) cSts.textContent = i18n.jobQueued;
+  }
+}
+
 export function UpdateAllUI() {
   renderRecentFiles();
   renderTabs();